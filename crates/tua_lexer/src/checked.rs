@@ -0,0 +1,98 @@
+//! Lexical errors collected from the flags on tokens.
+
+use alloc::vec::Vec;
+
+use crate::LiteralKind::*;
+use crate::TokenKind::*;
+use crate::{tokenize_with, LexOptions, Span, Token};
+
+/// Lexical error found by [`tokenize_checked`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LexError {
+    /// Span of the token with the error.
+    pub span: Span,
+    pub kind: LexErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LexErrorKind {
+    UnterminatedString,
+    UnterminatedLongString,
+    UnterminatedComment,
+    /// Number has a base prefix but no digits, e.g. `0x`.
+    EmptyNumber,
+    /// Number has an exponent mark but no digits after it, e.g. `1e`.
+    EmptyExponent,
+    /// Number is immediately followed by chars that can't continue it,
+    /// e.g. `3x` or `0x1p2.5`.
+    MalformedNumber,
+    InvalidEscape,
+    UnknownToken,
+}
+
+/// Tokenizes the whole input string into a vector, along with the errors
+/// flagged on the tokens, in source order.
+pub fn tokenize_checked(input: &str) -> (Vec<Token>, Vec<LexError>) {
+    tokenize_checked_with(input, LexOptions::default())
+}
+
+/// Like [`tokenize_checked`], lexing the dialect selected by `options`.
+pub fn tokenize_checked_with(input: &str, options: LexOptions) -> (Vec<Token>, Vec<LexError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut pos = 0;
+    for token in tokenize_with(input, options) {
+        let span = Span::new(pos, pos + token.len);
+        let mut error = |kind| errors.push(LexError { span, kind });
+        match token.kind {
+            LongComment {
+                terminated: false, ..
+            }
+            | BlockComment { terminated: false } => error(LexErrorKind::UnterminatedComment),
+            Literal {
+                kind:
+                    Number {
+                        empty_number,
+                        empty_exponent,
+                        malformed_suffix,
+                        ..
+                    },
+            } => {
+                if empty_number {
+                    error(LexErrorKind::EmptyNumber);
+                }
+                if empty_exponent {
+                    error(LexErrorKind::EmptyExponent);
+                }
+                if malformed_suffix {
+                    error(LexErrorKind::MalformedNumber);
+                }
+            }
+            Literal {
+                kind:
+                    ShortString {
+                        terminated,
+                        has_invalid_escape,
+                        ..
+                    },
+            } => {
+                if has_invalid_escape {
+                    error(LexErrorKind::InvalidEscape);
+                }
+                if !terminated {
+                    error(LexErrorKind::UnterminatedString);
+                }
+            }
+            Literal {
+                kind: LongString {
+                    terminated: false, ..
+                },
+            } => error(LexErrorKind::UnterminatedLongString),
+            Unknown => error(LexErrorKind::UnknownToken),
+            _ => (),
+        }
+        pos += token.len;
+        tokens.push(token);
+    }
+    (tokens, errors)
+}
@@ -1,7 +1,11 @@
-use std::str::Chars;
+use core::str::Chars;
+
+use crate::LexOptions;
 
 /// Peekable iterator over a char sequence.
+#[derive(Clone)]
 pub(crate) struct Cursor<'a> {
+    pub(crate) options: LexOptions,
     initial_len: usize,
     /// Iterator over chars. Slightly faster than a &str.
     chars: Chars<'a>,
@@ -11,9 +15,18 @@ pub(crate) struct Cursor<'a> {
 
 pub(crate) const EOF_CHAR: char = '\0';
 
+/// Saved position of a [`Lexer`](crate::Lexer), see
+/// [`Lexer::checkpoint`](crate::Lexer::checkpoint).
+#[derive(Clone, Debug)]
+pub struct Checkpoint<'a> {
+    chars: Chars<'a>,
+    initial_len: usize,
+}
+
 impl<'a> Cursor<'a> {
-    pub(crate) fn new(input: &'a str) -> Cursor<'a> {
+    pub(crate) fn new(input: &'a str, options: LexOptions) -> Cursor<'a> {
         Cursor {
+            options,
             initial_len: input.len(),
             chars: input.chars(),
             #[cfg(debug_assertions)]
@@ -44,6 +57,23 @@ impl<'a> Cursor<'a> {
         self.chars.clone().next().unwrap_or(EOF_CHAR)
     }
 
+    /// Peeks the second next symbol, like `peek`.
+    pub(crate) fn peek_second(&self) -> char {
+        let mut iter = self.chars.clone();
+        iter.next();
+        iter.next().unwrap_or(EOF_CHAR)
+    }
+
+    /// Returns the input that hasn't been consumed yet.
+    pub(crate) fn as_str(&self) -> &'a str {
+        self.chars.as_str()
+    }
+
+    /// Skips `len` bytes of input without looking at them.
+    pub(crate) fn skip(&mut self, len: usize) {
+        self.chars = self.chars.as_str()[len..].chars();
+    }
+
     /// Checks if there is nothing more to consume.
     pub(crate) fn is_eof(&self) -> bool {
         self.chars.as_str().is_empty()
@@ -51,6 +81,7 @@ impl<'a> Cursor<'a> {
 
     /// Returns amount of already consumed symbols.
     pub(crate) fn len_consumed(&self) -> u32 {
+        debug_assert!(self.initial_len >= self.chars.as_str().len());
         (self.initial_len - self.chars.as_str().len()) as u32
     }
 
@@ -59,6 +90,20 @@ impl<'a> Cursor<'a> {
         self.initial_len = self.chars.as_str().len();
     }
 
+    /// Saves the position of the cursor to go back to with `restore`.
+    pub(crate) fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint {
+            chars: self.chars.clone(),
+            initial_len: self.initial_len,
+        }
+    }
+
+    /// Moves the cursor back to a position saved with `checkpoint`.
+    pub(crate) fn restore(&mut self, checkpoint: Checkpoint<'a>) {
+        self.chars = checkpoint.chars;
+        self.initial_len = checkpoint.initial_len;
+    }
+
     /// Moves to the next character.
     pub(crate) fn consume(&mut self) -> Option<char> {
         let c = self.chars.next()?;
@@ -71,8 +116,53 @@ impl<'a> Cursor<'a> {
         Some(c)
     }
 
+    /// Consumes the next symbol if predicate returns true for it.
+    pub(crate) fn consume_if(&mut self, predicate: impl Fn(char) -> bool) -> bool {
+        if !self.is_eof() && predicate(self.peek()) {
+            self.consume();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes up to `n` symbols while predicate returns true.
+    /// Returns the number of consumed symbols.
+    pub(crate) fn consume_n_if(&mut self, n: usize, predicate: impl Fn(char) -> bool) -> usize {
+        let mut count = 0;
+        while count < n && self.consume_if(&predicate) {
+            count += 1;
+        }
+        count
+    }
+
     /// Consumes symbols while predicate returns true or until the end of file is reached.
     pub(crate) fn consume_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
+        self.consume_ascii_while(&mut predicate);
+        self.consume_chars_while(predicate);
+    }
+
+    /// Fast path of `consume_while`, which scans bytes without decoding
+    /// chars, and stops at the first non-ASCII char.
+    fn consume_ascii_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
+        let rest = self.chars.as_str();
+        let len = rest
+            .bytes()
+            .take_while(|&b| b.is_ascii() && predicate(char::from(b)))
+            .count();
+        if len == 0 {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            self.prev = char::from(rest.as_bytes()[len - 1]);
+        }
+
+        self.chars = rest[len..].chars();
+    }
+
+    fn consume_chars_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
         while !self.is_eof() && predicate(self.peek()) {
             self.consume();
         }
@@ -91,3 +181,99 @@ impl<'a> Cursor<'a> {
         count
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn len_consumed_after_reset() {
+        let mut cursor = Cursor::new("ab\u{e9}c", LexOptions::default());
+        assert_eq!(cursor.len_consumed(), 0);
+        cursor.consume();
+        assert_eq!(cursor.len_consumed(), 1);
+        cursor.reset_len_consumed();
+        assert_eq!(cursor.len_consumed(), 0);
+        cursor.consume();
+        cursor.consume();
+        // `é` is two bytes long.
+        assert_eq!(cursor.len_consumed(), 3);
+        cursor.consume();
+        assert!(cursor.is_eof());
+        assert_eq!(cursor.len_consumed(), 4);
+        cursor.reset_len_consumed();
+        assert_eq!(cursor.len_consumed(), 0);
+    }
+
+    #[test]
+    fn consume_if() {
+        let mut cursor = Cursor::new("ab", LexOptions::default());
+        assert!(!cursor.consume_if(|c| c == 'b'));
+        assert!(cursor.consume_if(|c| c == 'a'));
+        assert!(cursor.consume_if(|c| c == 'b'));
+        assert!(!cursor.consume_if(|_| true));
+        assert_eq!(cursor.len_consumed(), 2);
+    }
+
+    #[test]
+    fn consume_n_if() {
+        let is_digit = |c: char| c.is_ascii_digit();
+        let mut cursor = Cursor::new("12x", LexOptions::default());
+        assert_eq!(cursor.consume_n_if(3, is_digit), 2);
+        assert_eq!(cursor.peek(), 'x');
+        assert_eq!(cursor.consume_n_if(3, is_digit), 0);
+
+        let mut cursor = Cursor::new("1234", LexOptions::default());
+        assert_eq!(cursor.consume_n_if(2, is_digit), 2);
+        assert_eq!(cursor.len_consumed(), 2);
+        assert_eq!(cursor.consume_n_if(0, is_digit), 0);
+        assert_eq!(cursor.consume_n_if(5, is_digit), 2);
+        assert!(cursor.is_eof());
+    }
+
+    #[test]
+    fn consume_while_ascii_fast_path() {
+        let inputs = [
+            "",
+            "abc def",
+            "abc\u{e9}def ghi",
+            "\u{e9}\u{3c0}x y",
+            "a-b\n--\u{1F600}\r\nz",
+        ];
+        let predicates: [fn(char) -> bool; 3] = [
+            |c| c.is_alphanumeric(),
+            |c| c != '\n',
+            |c| c != '\u{e9}' && c != ' ',
+        ];
+        for input in inputs {
+            for predicate in predicates {
+                let mut fast = Cursor::new(input, LexOptions::default());
+                let mut chars = Cursor::new(input, LexOptions::default());
+                while !fast.is_eof() {
+                    fast.consume_while(predicate);
+                    chars.consume_chars_while(predicate);
+                    assert_eq!(fast.as_str(), chars.as_str(), "{:?}", input);
+                    assert_eq!(fast.prev(), chars.prev(), "{:?}", input);
+                    fast.consume();
+                    chars.consume();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn peek_second() {
+        let mut cursor = Cursor::new("ab\u{e9}", LexOptions::default());
+        assert_eq!(cursor.peek_second(), 'b');
+        assert_eq!(cursor.peek_second(), 'b');
+        assert_eq!(cursor.len_consumed(), 0);
+        cursor.consume();
+        assert_eq!(cursor.peek_second(), '\u{e9}');
+        cursor.consume();
+        assert_eq!(cursor.peek(), '\u{e9}');
+        assert_eq!(cursor.peek_second(), EOF_CHAR);
+        cursor.consume();
+        assert_eq!(cursor.peek_second(), EOF_CHAR);
+        assert!(cursor.is_eof());
+    }
+}
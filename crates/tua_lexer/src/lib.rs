@@ -17,23 +17,50 @@
 //! The main entity of this crate is the [`TokenKind`] enum which represents common
 //! lexeme types.
 //!
+//! The crate doesn't depend on `std` when built without the default `std`
+//! feature, so it can be used in `no_std` environments.
+//!
 // We want to be able to build this crate with a stable compiler, so no
 // `#![feature]` attributes should be added.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+mod checked;
 mod cursor;
+mod lint;
+mod op;
+mod spanned;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
 
 use self::LiteralKind::*;
 use self::TokenKind::*;
+pub use crate::checked::{tokenize_checked, tokenize_checked_with, LexError, LexErrorKind};
+pub use crate::cursor::Checkpoint;
 use crate::cursor::Cursor;
 use crate::cursor::EOF_CHAR;
+pub use crate::lint::{mixed_indentation, suspicious_operators};
+pub use crate::op::{binary_op, keyword_binary_op, keyword_unary_op, unary_op};
+pub use crate::op::{Assoc, BinOp, UnOp, UNARY_PRECEDENCE};
+pub use crate::spanned::{
+    dump_tokens_compact, relex, string_literals, token_at, token_containing, tokenize_indexed,
+    tokenize_lines, tokenize_spanned, tokenize_with_base, verify_coverage,
+};
+pub use crate::spanned::{
+    Bias, CoverageError, LineTokens, Span, SpannedToken, StringShape, TokenStream,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
 
 /// Parsed token.
 /// It doesn't contain information about data that has been parsed,
 /// only the type of the token and its size.
-#[derive(Debug)]
+///
+/// Every token is at least one byte long, and the lengths of all tokens
+/// produced from an input add up to the length of the input.
+#[derive(Debug, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
     pub len: u32,
@@ -45,17 +72,109 @@ impl Token {
     }
 }
 
+/// Source text paired with its tokens.
+///
+/// Iterating over it yields the kind and the source text of each token:
+///
+/// ```
+/// use tua_lexer::{tokenize, Lexed};
+///
+/// let src = "x = 1";
+/// let mut kinds = Vec::new();
+/// for (kind, _text) in Lexed::new(src) {
+///     kinds.push(kind);
+/// }
+/// let expected: Vec<_> = tokenize(src).map(|token| token.kind).collect();
+/// assert_eq!(kinds, expected);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Lexed<'a> {
+    src: &'a str,
+}
+
+impl<'a> Lexed<'a> {
+    pub fn new(src: &'a str) -> Lexed<'a> {
+        Lexed { src }
+    }
+
+    /// Returns an iterator over the kind and the source text of each token.
+    pub fn iter(&self) -> LexedIter<'a> {
+        LexedIter {
+            src: self.src,
+            tokens: tokenize(self.src),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for Lexed<'a> {
+    type Item = (TokenKind, &'a str);
+    type IntoIter = LexedIter<'a>;
+
+    fn into_iter(self) -> LexedIter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &Lexed<'a> {
+    type Item = (TokenKind, &'a str);
+    type IntoIter = LexedIter<'a>;
+
+    fn into_iter(self) -> LexedIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the tokens of [`Lexed`], returned by [`Lexed::iter`].
+#[derive(Clone)]
+pub struct LexedIter<'a> {
+    src: &'a str,
+    tokens: Tokens<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for LexedIter<'a> {
+    type Item = (TokenKind, &'a str);
+
+    fn next(&mut self) -> Option<(TokenKind, &'a str)> {
+        let token = self.tokens.next()?;
+        let start = self.offset;
+        self.offset += token.len as usize;
+        Some((token.kind, &self.src[start..self.offset]))
+    }
+}
+
+impl core::iter::FusedIterator for LexedIter<'_> {}
+
 /// Enum representing common lexeme types.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     // Multi-char tokens:
     /// `-- short comment`
+    /// Also a comment starting with an incomplete long bracket, e.g. `--[`
+    /// or `--[==`, which extends to the end of the line as in Lua.
     ShortComment,
+    /// `---@param x number`
+    /// Short comment starting with exactly three dashes and `@`, used for
+    /// annotations by Lua language servers.
+    DocComment,
     /// `--[[ long comment ]]`
     /// `--[=[ long comment ]=]`
-    LongComment { terminated: bool },
+    LongComment { level: usize, terminated: bool },
+    /// `/* block comment */`, see [`LexOptions::c_comments`].
+    BlockComment { terminated: bool },
     /// Any whitespace characters sequence.
-    Whitespace,
+    /// `has_newline` is set if the sequence contains a line break.
+    /// `has_unusual` is set if it contains a vertical tab, a form feed, or
+    /// a `\r` not followed by `\n`, which are rare and may indicate
+    /// a corrupted file.
+    Whitespace {
+        has_newline: bool,
+        has_unusual: bool,
+    },
+    /// `#!/usr/bin/env tua` on the first line.
+    /// Never produced by [`tokenize`], see [`tokenize_with_shebang`].
+    Shebang,
     /// Identifiers. At this step keywords are also considered identifiers.
     Ident,
     /// `"string"`, `3`, `314.16e-2`
@@ -69,6 +188,12 @@ pub enum TokenKind {
     Comma,
     /// "."
     Dot,
+    /// ".."
+    /// Only produced by [`glue_operators`].
+    DotDot,
+    /// "..."
+    /// Only produced by [`glue_operators`].
+    DotDotDot,
     /// "("
     OpenParen,
     /// ")"
@@ -85,14 +210,36 @@ pub enum TokenKind {
     Hash,
     /// "~"
     Tilde,
+    /// "~="
+    /// Only produced by [`glue_operators`].
+    TildeEq,
     /// ":"
     Colon,
+    /// "::"
+    /// Never produced by the lexer itself, which emits two `Colon`s,
+    /// see [`normalize_colons`] and [`glue_operators`].
+    ColonColon,
     /// "="
     Eq,
+    /// "=="
+    /// Only produced by [`glue_operators`].
+    EqEq,
     /// "<"
     Lt,
+    /// "<="
+    /// Only produced by [`glue_operators`].
+    LtEq,
+    /// "<<"
+    /// Only produced by [`glue_operators`].
+    LtLt,
     /// ">"
     Gt,
+    /// ">="
+    /// Only produced by [`glue_operators`].
+    GtEq,
+    /// ">>"
+    /// Only produced by [`glue_operators`].
+    GtGt,
     /// "-"
     Minus,
     /// "+"
@@ -101,36 +248,294 @@ pub enum TokenKind {
     Star,
     /// "/"
     Slash,
+    /// "//", since Lua 5.3.
+    SlashSlash,
     /// "^"
     Caret,
     /// "%"
     Percent,
+    /// "@", used by some Tua dialects for annotations.
+    At,
 
     /// Unknown token, not expected by the lexer.
     Unknown,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum LiteralKind {
     /// `3`, `3.0`, `3.1416`, `314.16e-2`, `0.31416E1`, `0xff`, `0x56`
     Number {
         base: NumberBase,
         empty_number: bool,
         empty_exponent: bool,
+        /// Sign of the exponent, e.g. `Some('-')` for `3e-5`. `None` if
+        /// the sign or the whole exponent is omitted. A sign not followed by
+        /// a digit isn't a part of the number, e.g. `3e-` is `3e`, `-`.
+        exponent_sign: Option<char>,
+        /// Literal contains a fractional part or an exponent, e.g. `3.0` or `0x1p4`.
+        is_float: bool,
+        /// Decimal literal has a redundant leading zero, e.g. `0755`, which
+        /// is likely an attempt to write an octal number.
+        leading_zero: bool,
+        /// Literal is immediately followed by an identifier, e.g. `3x` or `0x1g`,
+        /// a literal with an exponent by a `.`, e.g. `0x1p2.5`, or a binary or
+        /// octal literal by an out of range digit, e.g. `0o8`.
+        malformed_suffix: bool,
+        /// A `_` digit separator isn't between two digits, e.g. `1__0` or `1_`,
+        /// see [`LexOptions::digit_separators`].
+        misplaced_separator: bool,
     },
     /// `'abc'`, `"abc"`
-    ShortString { quote: char, terminated: bool },
+    ShortString {
+        quote: QuoteKind,
+        terminated: bool,
+        has_invalid_escape: bool,
+        /// A `\z` escape skipped over a line break, so the string spans
+        /// several lines.
+        crossed_newline: bool,
+    },
     /// `[[abc]]`, `[=[abc]=]`
-    LongString { level: usize, terminated: bool },
+    LongString {
+        level: usize,
+        terminated: bool,
+        /// A line break immediately follows the opening bracket. As in Lua,
+        /// it isn't a part of the string value, see [`long_bracket_content`].
+        skipped_leading_newline: bool,
+    },
+}
+
+impl LiteralKind {
+    /// Returns a numeric code of the variant, ignoring its fields, which is
+    /// stable like [`TokenKind::tag`].
+    pub fn tag(self) -> u16 {
+        match self {
+            Number { .. } => 0,
+            ShortString { .. } => 1,
+            LongString { .. } => 2,
+        }
+    }
+}
+
+/// Quote delimiting a `ShortString` literal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QuoteKind {
+    /// `'`
+    Single,
+    /// `"`
+    Double,
+}
+
+impl QuoteKind {
+    /// Returns the quote char, as written in the source.
+    pub fn as_char(self) -> char {
+        match self {
+            QuoteKind::Single => '\'',
+            QuoteKind::Double => '"',
+        }
+    }
 }
 
 /// Base of `Number` literal encoding according to its prefix.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum NumberBase {
     /// Literal doesn't contain a prefix.
     Decimal,
     /// Literal starts with `0x` or `0X`.
     Hexadecimal,
+    /// Literal starts with `0b` or `0B`, see [`LexOptions::binary_octal_literals`].
+    Binary,
+    /// Literal starts with `0o` or `0O`, see [`LexOptions::binary_octal_literals`].
+    Octal,
+}
+
+impl TokenKind {
+    /// Returns `true` for tokens that carry no meaning for the parser:
+    /// whitespace, comments and the shebang.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            Whitespace { .. }
+                | ShortComment
+                | DocComment
+                | LongComment { .. }
+                | BlockComment { .. }
+                | Shebang
+        )
+    }
+
+    /// Returns `true` for `Colon` and `ColonColon`.
+    pub fn is_colon_like(self) -> bool {
+        matches!(self, Colon | ColonColon)
+    }
+
+    /// Returns `true` for number literals of any base.
+    pub fn is_number(self) -> bool {
+        matches!(
+            self,
+            Literal {
+                kind: Number { .. }
+            }
+        )
+    }
+
+    /// Returns `true` for short and long string literals.
+    pub fn is_string(self) -> bool {
+        matches!(
+            self,
+            Literal {
+                kind: ShortString { .. } | LongString { .. }
+            }
+        )
+    }
+
+    /// Returns a numeric code of the variant, ignoring its fields, e.g. for
+    /// passing tokens to code not written in Rust.
+    ///
+    /// Codes are stable: a variant keeps its code in future versions, and new
+    /// variants get codes not used before. Use [`LiteralKind::tag`] to tell
+    /// the kinds of literals apart.
+    pub fn tag(self) -> u16 {
+        match self {
+            ShortComment => 0,
+            DocComment => 1,
+            LongComment { .. } => 2,
+            BlockComment { .. } => 3,
+            Whitespace { .. } => 4,
+            Shebang => 5,
+            Ident => 6,
+            Literal { .. } => 7,
+            Semi => 8,
+            Comma => 9,
+            Dot => 10,
+            DotDot => 11,
+            DotDotDot => 12,
+            OpenParen => 13,
+            CloseParen => 14,
+            OpenBrace => 15,
+            CloseBrace => 16,
+            OpenBracket => 17,
+            CloseBracket => 18,
+            Hash => 19,
+            Tilde => 20,
+            TildeEq => 21,
+            Colon => 22,
+            ColonColon => 23,
+            Eq => 24,
+            EqEq => 25,
+            Lt => 26,
+            LtEq => 27,
+            LtLt => 28,
+            Gt => 29,
+            GtEq => 30,
+            GtGt => 31,
+            Minus => 32,
+            Plus => 33,
+            Star => 34,
+            Slash => 35,
+            SlashSlash => 36,
+            Caret => 37,
+            Percent => 38,
+            At => 39,
+            Unknown => 40,
+        }
+    }
+
+    /// Returns `true` for comments of any kind, not including the shebang.
+    pub fn is_comment(self) -> bool {
+        matches!(
+            self,
+            ShortComment | DocComment | LongComment { .. } | BlockComment { .. }
+        )
+    }
+}
+
+impl core::fmt::Display for TokenKind {
+    /// Renders punctuation as it appears in the source, and other tokens as
+    /// a short description, e.g. `+` or `number literal`.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let s = match self {
+            ShortComment => "short comment",
+            DocComment => "doc comment",
+            LongComment { .. } => "long comment",
+            BlockComment { .. } => "block comment",
+            Whitespace { .. } => "whitespace",
+            Shebang => "shebang",
+            Ident => "identifier",
+            Literal { kind } => match kind {
+                Number { .. } => "number literal",
+                ShortString { .. } => "string literal",
+                LongString { .. } => "long string literal",
+            },
+            Semi => ";",
+            Comma => ",",
+            Dot => ".",
+            DotDot => "..",
+            DotDotDot => "...",
+            OpenParen => "(",
+            CloseParen => ")",
+            OpenBrace => "{",
+            CloseBrace => "}",
+            OpenBracket => "[",
+            CloseBracket => "]",
+            Hash => "#",
+            Tilde => "~",
+            TildeEq => "~=",
+            Colon => ":",
+            ColonColon => "::",
+            Eq => "=",
+            EqEq => "==",
+            Lt => "<",
+            LtEq => "<=",
+            LtLt => "<<",
+            Gt => ">",
+            GtEq => ">=",
+            GtGt => ">>",
+            Minus => "-",
+            Plus => "+",
+            Star => "*",
+            Slash => "/",
+            SlashSlash => "//",
+            Caret => "^",
+            Percent => "%",
+            At => "@",
+            Unknown => "unknown token",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Line ending style of a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r`
+    Cr,
+    /// More than one of the above.
+    Mixed,
+}
+
+/// Detects the line ending style used by the input.
+/// Input without any line breaks is considered to use `Lf`.
+pub fn detect_line_ending(input: &str) -> LineEnding {
+    let mut found = None;
+    let mut bytes = input.bytes().peekable();
+    while let Some(b) = bytes.next() {
+        let ending = match b {
+            b'\n' => LineEnding::Lf,
+            b'\r' if bytes.next_if_eq(&b'\n').is_some() => LineEnding::CrLf,
+            b'\r' => LineEnding::Cr,
+            _ => continue,
+        };
+        match found {
+            None => found = Some(ending),
+            Some(prev) if prev != ending => return LineEnding::Mixed,
+            Some(_) => (),
+        }
+    }
+    found.unwrap_or(LineEnding::Lf)
 }
 
 /// Tua allows files to have a hashbang, e.g. "#!/usr/bin/env tua",
@@ -145,20 +550,663 @@ pub fn strip_hashbang(input: &str) -> Option<usize> {
     None
 }
 
+/// Files may start with a UTF-8 byte order mark, which isn't a part of Tua
+/// syntax either. Returns the length of the BOM if the input starts with one.
+///
+/// The BOM should be stripped before tokenizing, and before looking for a
+/// hashbang with [`strip_hashbang`], since it precedes the `#!`.
+pub fn strip_bom(input: &str) -> Option<usize> {
+    if input.starts_with('\u{FEFF}') {
+        return Some('\u{FEFF}'.len_utf8());
+    }
+    None
+}
+
+/// Lua version whose syntax is lexed.
+///
+/// Token kinds that only appear for some versions:
+/// * `SlashSlash` (floor division), since Lua 5.3. Earlier versions lex `//`
+///   as two `Slash` tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LuaVersion {
+    Lua51,
+    Lua52,
+    Lua53,
+    #[default]
+    Lua54,
+}
+
+/// Options selecting the lexed dialect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LexOptions {
+    pub lua_version: LuaVersion,
+    /// Only accept ASCII letters, digits and `_` in identifiers, as C Lua does.
+    /// By default any other non-ASCII char is accepted too.
+    pub strict_idents: bool,
+    /// Lex `0b1010` binary and `0o17` octal integer literals, as some Tua
+    /// dialects do. By default `0b` and `0o` are a zero followed by
+    /// an identifier.
+    pub binary_octal_literals: bool,
+    /// Lex C-style `// line` comments as `ShortComment` and `/* block */`
+    /// comments as `BlockComment`, as some Tua dialects do. Then `//` always
+    /// starts a comment, and `SlashSlash` is never produced.
+    pub c_comments: bool,
+    /// Accept `_` separators in number literals, e.g. `1_000` or `0xFF_FF`,
+    /// as some Tua dialects do. By default `_` ends a number.
+    pub digit_separators: bool,
+}
+
 /// Creates an iterator that produces tokens from the input string.
-pub fn tokenize(input: &str) -> impl Iterator<Item = Token> + '_ {
-    let mut cursor = Cursor::new(input);
-    std::iter::from_fn(move || {
-        if cursor.is_eof() {
+///
+/// The input is expected to be stripped of a BOM and a hashbang, see
+/// [`strip_bom`] and [`strip_hashbang`]. A hashbang left in is lexed as
+/// `Hash` followed by an identifier starting with `!`, so for raw file
+/// contents prefer [`tokenize_with_shebang`].
+pub fn tokenize(input: &str) -> Tokens<'_> {
+    tokenize_with(input, LexOptions::default())
+}
+
+/// Creates an iterator that produces tokens from the input string, starting
+/// with a `Shebang` token if the input has a hashbang, see [`strip_hashbang`].
+///
+/// The input is expected to be stripped of a BOM.
+pub fn tokenize_with_shebang(input: &str) -> impl Iterator<Item = Token> + '_ {
+    let shebang_len = strip_hashbang(input);
+    let rest = &input[shebang_len.unwrap_or(0)..];
+    shebang_len
+        .map(|len| Token::new(Shebang, len as u32))
+        .into_iter()
+        .chain(tokenize(rest))
+}
+
+/// Creates an iterator that produces tokens from the input string,
+/// lexing the dialect selected by `options`.
+pub fn tokenize_with(input: &str, options: LexOptions) -> Tokens<'_> {
+    Tokens {
+        lexer: Lexer::with_options(input, options),
+    }
+}
+
+/// Iterator over the tokens of a string, returned by [`tokenize`].
+///
+/// Cloning it is cheap, so a parser can save its position and backtrack to
+/// it later.
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.lexer.next_token()
+    }
+}
+
+impl core::iter::FusedIterator for Tokens<'_> {}
+
+/// Lexer producing tokens one at a time.
+///
+/// Unlike [`tokenize`], it exposes the input that hasn't been lexed yet,
+/// so callers can interleave their own scanning with it.
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    cursor: Cursor<'a>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Lexer<'a> {
+        Lexer::with_options(input, LexOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: LexOptions) -> Lexer<'a> {
+        Lexer {
+            cursor: Cursor::new(input, options),
+        }
+    }
+
+    /// Parses the next token, or returns `None` at the end of input.
+    pub fn next_token(&mut self) -> Option<Token> {
+        if self.cursor.is_eof() {
             None
         } else {
-            cursor.reset_len_consumed();
-            Some(cursor.advance_token())
+            self.cursor.reset_len_consumed();
+            Some(self.cursor.advance_token())
+        }
+    }
+
+    /// Returns the input that hasn't been lexed yet.
+    pub fn remaining(&self) -> &'a str {
+        self.cursor.as_str()
+    }
+
+    /// Skips `len` bytes of the remaining input, e.g. after scanning them
+    /// with custom logic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is past the end of the remaining input or isn't on
+    /// a char boundary.
+    pub fn skip(&mut self, len: usize) {
+        self.cursor.skip(len);
+    }
+
+    /// Saves the position of the lexer, e.g. before parsing speculatively.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        self.cursor.checkpoint()
+    }
+
+    /// Moves the lexer back to a position saved with [`Lexer::checkpoint`].
+    ///
+    /// The checkpoint should come from this lexer, otherwise the lexer
+    /// continues with the input of the other one.
+    pub fn restore(&mut self, checkpoint: Checkpoint<'a>) {
+        self.cursor.restore(checkpoint);
+    }
+}
+
+/// Tokenizes the whole input string into a vector.
+///
+/// The vector is preallocated for `input.len() / 4` tokens, which is
+/// roughly the density of typical Lua sources, to avoid reallocations
+/// while collecting large files.
+pub fn tokenize_to_vec(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::with_capacity(input.len() / 4);
+    tokens.extend(tokenize(input));
+    tokens
+}
+
+/// Returns the length of the longest token of the input string, or 0 if it's
+/// empty, e.g. to size a buffer for any token.
+pub fn max_token_len(input: &str) -> u32 {
+    tokenize(input).map(|token| token.len).max().unwrap_or(0)
+}
+
+/// Error returned by [`tokenize_limited`] and [`tokenize_limited_bytes`] when
+/// the input exceeds the limit.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// Tokens lexed before the limit was hit, e.g. to report where it was.
+    pub tokens: Vec<Token>,
+}
+
+/// Tokenizes the input string into a vector of at most `max_tokens` tokens,
+/// e.g. to bound the memory used for untrusted input.
+///
+/// If the input has more tokens, the first `max_tokens` of them are returned
+/// in the error.
+pub fn tokenize_limited(input: &str, max_tokens: usize) -> Result<Vec<Token>, LimitExceeded> {
+    let mut tokens = tokenize(input);
+    let limited: Vec<_> = tokens.by_ref().take(max_tokens).collect();
+    if tokens.next().is_some() {
+        return Err(LimitExceeded { tokens: limited });
+    }
+    Ok(limited)
+}
+
+/// Tokenizes the input string into a vector if it's at most `max_bytes` long.
+///
+/// Unlike [`tokenize_limited`], this bounds the time spent on a single huge
+/// token, e.g. an unterminated string, since no more than `max_bytes` of
+/// the input are ever lexed. If the input is longer, the error has the tokens
+/// of its first `max_bytes` that are lexed as in the whole input. Tokens
+/// ending too close to the cut for the lexer to see past them are left out,
+/// e.g. `3e+5` cut after `3e+` would be lexed as `3e`, `+`.
+pub fn tokenize_limited_bytes(input: &str, max_bytes: usize) -> Result<Vec<Token>, LimitExceeded> {
+    if input.len() <= max_bytes {
+        return Ok(tokenize_to_vec(input));
+    }
+    let mut end = max_bytes;
+    while !input.is_char_boundary(end) {
+        end -= 1;
+    }
+    let prefix = &input[..end];
+    let mut tokens = tokenize_to_vec(prefix);
+    let mut token_end = prefix.len();
+    while let Some(token) = tokens.last() {
+        if prefix[token_end..].chars().nth(LOOKAHEAD - 1).is_some() {
+            break;
+        }
+        token_end -= token.len as usize;
+        tokens.pop();
+    }
+    Err(LimitExceeded { tokens })
+}
+
+/// Error returned by [`lex_single`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SingleTokenError {
+    /// The input is empty.
+    Empty,
+    /// The input has more after its first token, which is `len` bytes long.
+    Trailing { len: u32 },
+}
+
+/// Lexes a string consisting of exactly one token, e.g. `foo` or `==`.
+///
+/// Multi-char operators are glued as by [`glue_operators`].
+pub fn lex_single(s: &str) -> Result<Token, SingleTokenError> {
+    let mut tokens = glue_operators(tokenize(s));
+    let token = tokens.next().ok_or(SingleTokenError::Empty)?;
+    if tokens.next().is_some() {
+        return Err(SingleTokenError::Trailing { len: token.len });
+    }
+    Ok(token)
+}
+
+/// Parses the first token from the provided input string.
+fn first_token(input: &str) -> Token {
+    debug_assert!(!input.is_empty());
+    Cursor::new(input, LexOptions::default()).advance_token()
+}
+
+/// Number of chars that the lexer may look at past the end of a token
+/// to decide where it ends.
+const LOOKAHEAD: usize = 2;
+
+/// Creates an iterator that produces tokens from a stream of chars, without
+/// requiring the whole input to be in memory.
+///
+/// Chars are buffered until a token and the lookahead after it are
+/// available, so memory usage is proportional to the longest token.
+/// Token lengths are in bytes of the UTF-8 encoding of the chars, exactly as
+/// [`tokenize`] would report them for the collected input.
+pub fn tokenize_chars<I: Iterator<Item = char>>(chars: I) -> impl Iterator<Item = Token> {
+    const MIN_CHUNK: usize = 64;
+
+    let mut chars = chars.fuse();
+    let mut buf = String::new();
+    // Offset of the first char in `buf` not lexed yet.
+    let mut start = 0;
+    let mut eof = false;
+
+    // Drops the lexed chars from the buffer and reads more chars into it,
+    // at least doubling the length of the rest. Lexed chars are only dropped
+    // here, so the cost of moving the rest is amortized over the refill.
+    let mut refill = move |buf: &mut String, start: &mut usize| {
+        buf.drain(..*start);
+        *start = 0;
+        let want = buf.len().max(MIN_CHUNK);
+        let before = buf.len();
+        buf.extend(chars.by_ref().take(want));
+        buf.len() - before < want
+    };
+
+    core::iter::from_fn(move || loop {
+        let rest = &buf[start..];
+        if rest.is_empty() {
+            if eof {
+                return None;
+            }
+            eof = refill(&mut buf, &mut start);
+            continue;
+        }
+        let token = first_token(rest);
+        let len = token.len as usize;
+        // The token is final only if the lexer could see past its end.
+        if eof || rest[len..].chars().nth(LOOKAHEAD - 1).is_some() {
+            start += len;
+            return Some(token);
+        }
+        eof = refill(&mut buf, &mut start);
+    })
+}
+
+/// Creates an iterator that produces tokens from bytes that may not be valid
+/// UTF-8.
+///
+/// Each invalid sequence is replaced with U+FFFD, as
+/// [`String::from_utf8_lossy`] does, which lexes as an identifier char unless
+/// [`LexOptions::strict_idents`] is set. Token lengths are in bytes of the
+/// decoded string, not of `input`.
+pub fn tokenize_bytes(input: &[u8]) -> impl Iterator<Item = Token> + '_ {
+    let chars = input.utf8_chunks().flat_map(|chunk| {
+        let replacement = (!chunk.invalid().is_empty()).then_some(char::REPLACEMENT_CHARACTER);
+        chunk.valid().chars().chain(replacement)
+    });
+    tokenize_chars(chars)
+}
+
+/// Creates an iterator that produces tokens from the input string starting
+/// at the byte offset `start`.
+///
+/// Resuming is only valid at a known token boundary: a long string or a long
+/// comment started before `start` isn't detected, and its tail is lexed as
+/// ordinary tokens. If `start` is out of range or isn't a char boundary,
+/// the iterator is empty.
+pub fn tokenize_from(input: &str, start: usize) -> impl Iterator<Item = Token> + '_ {
+    tokenize(input.get(start..).unwrap_or_default())
+}
+
+/// Creates an iterator that produces tokens from the input string, with
+/// their byte offsets, skipping trivia (see [`TokenKind::is_trivia`]).
+///
+/// Offsets account for the skipped tokens, so the source text of every token
+/// can still be found, and the gaps between tokens are the trivia.
+pub fn tokenize_significant(input: &str) -> impl Iterator<Item = SpannedToken> + '_ {
+    tokenize_spanned(input).filter(|token| !token.kind.is_trivia())
+}
+
+/// Significant token together with the trivia preceding it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TokenWithTrivia {
+    /// Whitespace and comments between the previous significant token and
+    /// this one.
+    pub leading: Vec<Token>,
+    /// The significant token, or `None` for the trivia at the end of
+    /// the input, after the last significant token.
+    pub token: Option<Token>,
+}
+
+/// Creates an iterator that produces significant tokens from the input
+/// string, each with the trivia preceding it attached.
+///
+/// If the input ends with trivia, it's produced last, with no token. So no
+/// input is lost, and the lengths of all tokens add up to the length of
+/// the input.
+pub fn tokenize_with_trivia(input: &str) -> impl Iterator<Item = TokenWithTrivia> + '_ {
+    let mut tokens = tokenize(input);
+    core::iter::from_fn(move || {
+        let mut leading = Vec::new();
+        for token in tokens.by_ref() {
+            if !token.kind.is_trivia() {
+                return Some(TokenWithTrivia {
+                    leading,
+                    token: Some(token),
+                });
+            }
+            leading.push(token);
+        }
+        (!leading.is_empty()).then_some(TokenWithTrivia {
+            leading,
+            token: None,
+        })
+    })
+}
+
+/// Folds runs of adjacent `Unknown` tokens into a single token, so a run of
+/// garbage is reported once.
+pub fn coalesce_unknown(tokens: impl Iterator<Item = Token>) -> impl Iterator<Item = Token> {
+    let mut tokens = tokens.peekable();
+    core::iter::from_fn(move || {
+        let mut token = tokens.next()?;
+        if token.kind == Unknown {
+            while let Some(next) = tokens.next_if(|next| next.kind == Unknown) {
+                token.len += next.len;
+            }
+        }
+        Some(token)
+    })
+}
+
+/// Checks if `ident` is a Lua 5.4 variable attribute name, as in
+/// `local x <const> = 1`.
+///
+/// The lexer doesn't produce a dedicated token for attributes: `<const>` is
+/// lexed as `Lt`, `Ident`, `Gt`. A parser should treat these three tokens as
+/// an attribute when they immediately follow a name in a `local` declaration
+/// and the identifier satisfies this function.
+pub fn is_attribute_name(ident: &str) -> bool {
+    matches!(ident, "const" | "close")
+}
+
+/// Rewrites pairs of adjacent `Colon` tokens into a single `ColonColon`,
+/// as used by `goto` labels, e.g. `::label::`.
+pub fn normalize_colons(tokens: impl Iterator<Item = Token>) -> impl Iterator<Item = Token> {
+    let mut tokens = tokens.peekable();
+    core::iter::from_fn(move || {
+        let token = tokens.next()?;
+        if token.kind == Colon {
+            if let Some(next) = tokens.next_if(|next| next.kind == Colon) {
+                return Some(Token::new(ColonColon, token.len + next.len));
+            }
+        }
+        Some(token)
+    })
+}
+
+/// Counts and total lengths of tokens by category, see [`token_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenStats {
+    pub comments: usize,
+    pub comment_bytes: usize,
+    pub strings: usize,
+    pub string_bytes: usize,
+    pub numbers: usize,
+    pub number_bytes: usize,
+    pub idents: usize,
+    pub ident_bytes: usize,
+    /// Punctuation tokens, e.g. `+` or `(`.
+    pub operators: usize,
+    pub operator_bytes: usize,
+}
+
+/// Collects [`TokenStats`] of the input string.
+///
+/// Whitespace and `Unknown` tokens belong to no category and aren't counted.
+pub fn token_stats(input: &str) -> TokenStats {
+    let mut stats = TokenStats::default();
+    for token in tokenize(input) {
+        let (count, bytes) = match token.kind {
+            ShortComment | DocComment | LongComment { .. } | BlockComment { .. } => {
+                (&mut stats.comments, &mut stats.comment_bytes)
+            }
+            Literal {
+                kind: ShortString { .. } | LongString { .. },
+            } => (&mut stats.strings, &mut stats.string_bytes),
+            Literal {
+                kind: Number { .. },
+            } => (&mut stats.numbers, &mut stats.number_bytes),
+            Ident => (&mut stats.idents, &mut stats.ident_bytes),
+            Whitespace { .. } | Shebang | Unknown => continue,
+            _ => (&mut stats.operators, &mut stats.operator_bytes),
+        };
+        *count += 1;
+        *bytes += token.len as usize;
+    }
+    stats
+}
+
+/// Checks if the number literal `src` of the given `kind` is out of range:
+/// a float rounding to infinity, a hexadecimal, binary or octal integer not
+/// fitting 64 bits, which Lua wraps around, or a decimal integer not fitting
+/// `i64` and not exactly representable as the float Lua converts it to.
+///
+/// Returns `false` for other kinds and for malformed numbers. The lexer
+/// doesn't check this itself to stay fast. Digit separators, see
+/// [`LexOptions::digit_separators`], are ignored.
+pub fn number_overflows(src: &str, kind: LiteralKind) -> bool {
+    let Number {
+        base,
+        empty_number,
+        empty_exponent,
+        is_float,
+        ..
+    } = kind
+    else {
+        return false;
+    };
+    if empty_number || empty_exponent {
+        return false;
+    }
+    // A `_` is only a part of a number with digit separators enabled.
+    let stripped: String;
+    let src = if src.contains('_') {
+        stripped = src.chars().filter(|&c| c != '_').collect();
+        stripped.as_str()
+    } else {
+        src
+    };
+    let radix = match base {
+        NumberBase::Decimal if is_float => {
+            return src.parse::<f64>().is_ok_and(f64::is_infinite);
+        }
+        NumberBase::Decimal => {
+            if src.parse::<i64>().is_ok() {
+                return false;
+            }
+            return !decimal_is_exact_float(src.trim_start_matches('0'));
+        }
+        NumberBase::Hexadecimal if is_float => return hex_float_overflows(&src[2..]),
+        NumberBase::Hexadecimal => 16,
+        NumberBase::Binary => 2,
+        NumberBase::Octal => 8,
+    };
+    let digits = src[2..].trim_start_matches('0');
+    !digits.is_empty() && u64::from_str_radix(digits, radix).is_err()
+}
+
+/// Checks if the decimal integer `digits`, without leading zeros, is exactly
+/// representable as an `f64`, that is if it's an odd number below 2^53 times
+/// a power of two, and not above `f64::MAX`.
+fn decimal_is_exact_float(digits: &str) -> bool {
+    // `f64::MAX` has 309 decimal digits.
+    if digits.len() > 309 || !digits.parse::<f64>().is_ok_and(f64::is_finite) {
+        return false;
+    }
+    let mut digits: Vec<u8> = digits.bytes().map(|b| b - b'0').collect();
+    // Divide by two while even, in the decimal digits.
+    while digits.last().is_some_and(|&d| d % 2 == 0) {
+        let mut carry = 0;
+        for d in &mut digits {
+            let n = carry * 10 + *d;
+            *d = n / 2;
+            carry = n % 2;
         }
+        if digits[0] == 0 {
+            digits.remove(0);
+        }
+    }
+    digits.len() <= 16
+        && digits.iter().fold(0u64, |n, &d| n * 10 + u64::from(d)) < 1 << f64::MANTISSA_DIGITS
+}
+
+/// Checks if a hex float, without the `0x` prefix, rounds to infinity.
+fn hex_float_overflows(src: &str) -> bool {
+    let (mantissa, exponent) = src.split_once(['p', 'P']).unwrap_or((src, "0"));
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    // Hex digits of the mantissa without the fraction point and leading zeros.
+    let digits = || {
+        int.chars()
+            .chain(frac.chars())
+            .skip_while(|&c| c == '0')
+            .filter_map(|c| c.to_digit(16))
+    };
+    let Some(first) = digits().next() else {
+        // Zero, whatever the exponent.
+        return false;
+    };
+    let Ok(exponent) = exponent.parse::<i64>() else {
+        // The exponent doesn't even fit `i64`, so the number either underflows
+        // to zero or overflows.
+        return !exponent.starts_with('-');
+    };
+    // Number of binary digits in the mantissa, and the power of two of its
+    // highest digit.
+    let first_bits = u32::BITS - first.leading_zeros();
+    let bits = 4 * (digits().count() as i64 - 1) + i64::from(first_bits);
+    let highest_exponent = exponent
+        .saturating_sub(4 * frac.len() as i64)
+        .saturating_add(bits - 1);
+    if highest_exponent != 1023 {
+        return highest_exponent > 1023;
+    }
+    // Below 2^1024, the number rounds to infinity only if it's at least
+    // halfway from `f64::MAX` to 2^1024, that is if its highest 54 bits,
+    // one more than `f64` stores, are all ones.
+    let bits = digits().enumerate().flat_map(|(i, digit)| {
+        let width = if i == 0 { first_bits } else { 4 };
+        (0..width).rev().map(move |bit| digit >> bit & 1)
+    });
+    let rounding_bits = f64::MANTISSA_DIGITS as usize + 1;
+    bits.take(rounding_bits).filter(|&bit| bit == 1).count() == rounding_bits
+}
+
+/// Returns the content of a long bracket of the given `level`, without
+/// the delimiters.
+///
+/// `src` is the text of a `LongString` token, or of a `LongComment` token
+/// without its leading `--`. As in Lua, a line break immediately following
+/// the opening bracket isn't a part of the content. If the bracket is
+/// unterminated, only the opening bracket is stripped.
+pub fn long_bracket_content(src: &str, level: usize) -> &str {
+    let delim_len = level + 2;
+    let content = src.get(delim_len..).unwrap_or_default();
+    let content = ["\r\n", "\n\r", "\n", "\r"]
+        .iter()
+        .find_map(|newline| content.strip_prefix(newline))
+        .unwrap_or(content);
+    let bytes = content.as_bytes();
+    let n = bytes.len();
+    let terminated = n >= delim_len
+        && bytes[n - delim_len] == b']'
+        && bytes[n - delim_len + 1..n - 1].iter().all(|&b| b == b'=')
+        && bytes[n - 1] == b']';
+    if terminated {
+        &content[..n - delim_len]
+    } else {
+        content
+    }
+}
+
+/// Rewrites adjacent one-char tokens into the multi-char operators they
+/// form, e.g. `Dot`, `Dot` into `DotDot`.
+///
+/// As in Lua, the longest operator is taken from left to right, so `...`
+/// becomes `DotDotDot` and `<<=` becomes `LtLt`, `Eq`. Colons are glued as
+/// by [`normalize_colons`].
+pub fn glue_operators(tokens: impl Iterator<Item = Token>) -> impl Iterator<Item = Token> {
+    let glue = |first: TokenKind, second: TokenKind| match (first, second) {
+        (Dot, Dot) => Some(DotDot),
+        (DotDot, Dot) => Some(DotDotDot),
+        (Colon, Colon) => Some(ColonColon),
+        (Tilde, Eq) => Some(TildeEq),
+        (Eq, Eq) => Some(EqEq),
+        (Lt, Eq) => Some(LtEq),
+        (Lt, Lt) => Some(LtLt),
+        (Gt, Eq) => Some(GtEq),
+        (Gt, Gt) => Some(GtGt),
+        _ => None,
+    };
+    let mut tokens = tokens.peekable();
+    core::iter::from_fn(move || {
+        let mut token = tokens.next()?;
+        while let Some(kind) = tokens.peek().and_then(|next| glue(token.kind, next.kind)) {
+            let next = tokens.next().unwrap();
+            token = Token::new(kind, token.len + next.len);
+        }
+        Some(token)
+    })
+}
+
+/// Checks if a `_` in the number literal text `digits`, without its first
+/// digit, isn't between two digits of `base`.
+fn has_misplaced_separator(digits: &str, base: NumberBase) -> bool {
+    let is_digit = |b: u8| match base {
+        NumberBase::Decimal => b.is_ascii_digit(),
+        NumberBase::Hexadecimal => b.is_ascii_hexdigit(),
+        NumberBase::Binary => matches!(b, b'0' | b'1'),
+        NumberBase::Octal => matches!(b, b'0'..=b'7'),
+    };
+    let bytes = digits.as_bytes();
+    bytes.iter().enumerate().any(|(i, &b)| {
+        // The first digit of the literal precedes `digits`.
+        let after_digit = i == 0 || is_digit(bytes[i - 1]);
+        let before_digit = bytes.get(i + 1).is_some_and(|&next| is_digit(next));
+        b == b'_' && !(after_digit && before_digit)
     })
 }
 
-fn is_whitespace(c: char) -> bool {
+/// Checks if `c` is considered whitespace by Tua.
+///
+/// Unlike [`char::is_whitespace`], only ASCII whitespace is accepted.
+///
+/// ```
+/// assert!(tua_lexer::is_whitespace('\t'));
+/// assert!(!tua_lexer::is_whitespace('\u{00A0}'));
+/// ```
+pub fn is_whitespace(c: char) -> bool {
     matches!(
         c,
         '\u{0009}'   // \t
@@ -170,8 +1218,17 @@ fn is_whitespace(c: char) -> bool {
     )
 }
 
-/// checks if `c` is valid as a non-first character of an identifier.
-fn is_ident_continue(c: char) -> bool {
+/// Checks if `c` is valid as a non-first character of an identifier.
+///
+/// Any char other than ASCII control chars, whitespace and punctuation used
+/// by Tua is accepted. With [`LexOptions::strict_idents`] the lexer only
+/// accepts ASCII letters, digits and `_` instead.
+///
+/// ```
+/// assert!(tua_lexer::is_ident_continue('π'));
+/// assert!(!tua_lexer::is_ident_continue('+'));
+/// ```
+pub fn is_ident_continue(c: char) -> bool {
     !c.is_ascii_control()
         && !is_whitespace(c)
         && !matches!(
@@ -201,21 +1258,48 @@ fn is_ident_continue(c: char) -> bool {
                 | '\\'
                 | '\''
                 | '"'
+                | '@'
         )
 }
 
-/// checks if `c` is valid as a first character of an identifier.
-fn is_ident_start(c: char) -> bool {
+/// Checks if `c` is valid as a first character of an identifier, that is
+/// [`is_ident_continue`] and not a digit.
+///
+/// ```
+/// assert!(tua_lexer::is_ident_start('_'));
+/// assert!(!tua_lexer::is_ident_start('1'));
+/// ```
+pub fn is_ident_start(c: char) -> bool {
     !c.is_ascii_digit() && is_ident_continue(c)
 }
 
+/// checks if `c` is valid as a non-first character of an identifier in C Lua.
+fn is_ident_continue_strict(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// checks if `c` is valid as a first character of an identifier in C Lua.
+fn is_ident_start_strict(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
 impl Cursor<'_> {
+    /// checks if `c` is valid as a first character of an identifier
+    /// in the lexed dialect.
+    fn is_ident_start(&self, c: char) -> bool {
+        if self.options.strict_idents {
+            is_ident_start_strict(c)
+        } else {
+            is_ident_start(c)
+        }
+    }
+
     /// Parses a token from the input string.
     fn advance_token(&mut self) -> Token {
         let first_char = self.consume().unwrap();
         let token_kind = match first_char {
             // Whitespace sequence.
-            c if is_whitespace(c) => self.whitespace(),
+            c if is_whitespace(c) => self.whitespace(c),
 
             // Minus or comment
             '-' => match self.peek() {
@@ -251,204 +1335,283 @@ impl Cursor<'_> {
             '>' => Gt,
             '+' => Plus,
             '*' => Star,
-            '/' => Slash,
+            '/' => match self.peek() {
+                '/' if self.options.c_comments => self.short_comment(),
+                '*' if self.options.c_comments => self.block_comment(),
+                '/' if self.options.lua_version >= LuaVersion::Lua53 => {
+                    self.consume();
+                    SlashSlash
+                }
+                _ => Slash,
+            },
             '^' => Caret,
             '%' => Percent,
+            '@' => At,
 
             // Identifier.
-            c if is_ident_start(c) => {
-                self.consume_while(is_ident_continue);
+            c if self.is_ident_start(c) => {
+                if self.options.strict_idents {
+                    self.consume_while(is_ident_continue_strict);
+                } else {
+                    self.consume_while(is_ident_continue);
+                }
                 Ident
             }
 
             _ => Unknown,
         };
-        Token::new(token_kind, self.len_consumed())
+        let len = self.len_consumed();
+        debug_assert!(len > 0, "token must consume at least one byte");
+        Token::new(token_kind, len)
     }
 
-    fn comment(&mut self) -> TokenKind {
-        debug_assert!(self.prev() == '-' && self.peek() == '-');
-        self.consume();
+    /// Consumes the rest of a short comment. Like in Lua, the comment ends
+    /// before either `\n` or `\r`, so the line break is never a part of it.
+    fn short_comment(&mut self) -> TokenKind {
+        self.consume_while(|c| c != '\n' && c != '\r');
+        ShortComment
+    }
 
-        match self.peek() {
-            '[' => {
+    fn block_comment(&mut self) -> TokenKind {
+        debug_assert!(self.prev() == '/' && self.peek() == '*');
+        self.consume();
+        while let Some(c) = self.consume() {
+            if c == '*' && self.peek() == '/' {
                 self.consume();
-                let open_level = self.count_and_consume_while(|c| c == '=');
-                match self.peek() {
-                    '[' => {
-                        while let Some(c) = self.consume() {
-                            match c {
-                                ']' => {
-                                    let close_level = self.count_and_consume_while(|c| c == '=');
-                                    if open_level == close_level && self.peek() == ']' {
-                                        self.consume();
-                                        return LongComment { terminated: true };
-                                    }
-                                }
-                                _ => (),
-                            }
-                        }
-                        LongComment { terminated: false }
-                    }
-                    _ => {
-                        self.consume_while(|c| c != '\n');
-                        ShortComment
-                    }
-                }
-            }
-            _ => {
-                self.consume_while(|c| c != '\n');
-                ShortComment
+                return BlockComment { terminated: true };
             }
         }
+        BlockComment { terminated: false }
     }
 
-    fn consume_decimal_digits(&mut self) -> bool {
-        let mut has_digits = false;
-        loop {
-            match self.peek() {
-                '0'..='9' => {
-                    has_digits = true;
+    fn comment(&mut self) -> TokenKind {
+        debug_assert!(self.prev() == '-' && self.peek() == '-');
+        self.consume();
+
+        if self.peek() == '-' && self.peek_second() == '@' {
+            self.short_comment();
+            return DocComment;
+        }
+        // A long bracket may only start with `[[` or `[=`.
+        if self.peek() != '[' || !matches!(self.peek_second(), '[' | '=') {
+            return self.short_comment();
+        }
+        self.consume();
+        let open_level = self.count_and_consume_while(|c| c == '=');
+        // Not a long bracket after all, e.g. `--[=x` or `--[=` at the end of
+        // the input. The consumed chars are a part of the short comment.
+        if self.peek() != '[' {
+            return self.short_comment();
+        }
+        while let Some(c) = self.consume() {
+            if c == ']' {
+                let close_level = self.count_and_consume_while(|c| c == '=');
+                if open_level == close_level && self.peek() == ']' {
                     self.consume();
+                    return LongComment {
+                        level: open_level,
+                        terminated: true,
+                    };
                 }
-                _ => break,
             }
         }
-        has_digits
+        LongComment {
+            level: open_level,
+            terminated: false,
+        }
     }
 
-    fn consume_hexadecimal_digits(&mut self) -> bool {
+    /// Consumes digits, and `_` separators if [`LexOptions::digit_separators`]
+    /// is set. Returns `true` if at least one digit was met.
+    fn consume_digits(&mut self, is_digit: impl Fn(char) -> bool) -> bool {
         let mut has_digits = false;
         loop {
-            match self.peek() {
-                '0'..='9' | 'a'..='f' | 'A'..='F' => {
-                    has_digits = true;
-                    self.consume();
-                }
-                _ => break,
+            let c = self.peek();
+            if is_digit(c) {
+                has_digits = true;
+            } else if !(c == '_' && self.options.digit_separators) {
+                break;
             }
+            self.consume();
         }
         has_digits
     }
 
-    /// Consumes the number exponent. Returns `true` if at least one digit was met,
-    /// and returns `false` otherwise.
-    fn consume_number_exponent(&mut self) -> bool {
+    fn consume_decimal_digits(&mut self) -> bool {
+        self.consume_digits(|c| c.is_ascii_digit())
+    }
+
+    fn consume_binary_digits(&mut self) -> bool {
+        self.consume_digits(|c| matches!(c, '0' | '1'))
+    }
+
+    fn consume_octal_digits(&mut self) -> bool {
+        self.consume_digits(|c| matches!(c, '0'..='7'))
+    }
+
+    fn consume_hexadecimal_digits(&mut self) -> bool {
+        self.consume_digits(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Consumes the number exponent. Returns its sign, if any, and `true` if
+    /// at least one digit was met, `false` otherwise.
+    fn consume_number_exponent(&mut self) -> (Option<char>, bool) {
         debug_assert!(
             self.prev() == 'e' || self.prev() == 'E' || self.prev() == 'p' || self.prev() == 'P'
         );
-        if self.peek() == '-' || self.peek() == '+' {
-            self.consume();
+        // The sign is a part of the number only if a digit follows it, so
+        // `3e+x` is lexed as `3e`, `+`, `x`.
+        let mut sign = None;
+        if matches!(self.peek(), '-' | '+') && self.peek_second().is_ascii_digit() {
+            sign = self.consume();
         }
-        self.consume_decimal_digits()
+        (sign, self.consume_decimal_digits())
     }
 
     fn number(&mut self, first_digit: char) -> TokenKind {
         debug_assert!(self.prev() == first_digit && '0' <= self.prev() && self.prev() <= '9');
         let mut base = NumberBase::Decimal;
-        if first_digit == '0' {
-            // Attempt to parse encoding base.
-            let has_digits = match self.peek() {
-                'x' | 'X' => {
-                    base = NumberBase::Hexadecimal;
-                    self.consume();
-                    self.consume_hexadecimal_digits()
-                }
-                // Not a base prefix.
-                '0'..='9' | '.' | 'e' | 'E' => {
-                    self.consume_decimal_digits();
-                    true
-                }
-                // Just a `0`.
-                _ => {
-                    return Literal {
-                        kind: Number {
-                            base,
-                            empty_exponent: true,
-                            empty_number: false,
-                        },
-                    }
-                }
+        let mut empty_number = false;
+        let mut empty_exponent = false;
+        let mut exponent_sign = None;
+        let mut is_float = false;
+        let mut has_exponent = false;
+        let mut leading_zero = false;
+        let rest = self.as_str();
+
+        if first_digit == '0' && matches!(self.peek(), 'x' | 'X') {
+            base = NumberBase::Hexadecimal;
+            self.consume();
+            // The integer part may be omitted in a hex float, e.g. `0x.8`,
+            // then the number is empty only if the fractional part is empty too.
+            empty_number = !self.consume_hexadecimal_digits();
+        } else if first_digit == '0'
+            && self.options.binary_octal_literals
+            && matches!(self.peek(), 'b' | 'B' | 'o' | 'O')
+        {
+            let has_digits = if matches!(self.consume(), Some('b' | 'B')) {
+                base = NumberBase::Binary;
+                self.consume_binary_digits()
+            } else {
+                base = NumberBase::Octal;
+                self.consume_octal_digits()
             };
-            // Base prefix was provided, but there were no digits
-            // after it, e.g. `0x`.
-            if !has_digits {
-                return Literal {
-                    kind: Number {
-                        base,
-                        empty_exponent: false,
-                        empty_number: true,
-                    },
-                };
-            }
+            empty_number = !has_digits;
         } else {
             // No base prefix, parse number in the usual way.
-            self.consume_decimal_digits();
+            leading_zero = self.consume_decimal_digits() && first_digit == '0';
+        }
+
+        // Binary and octal literals are integers only, so there are no
+        // fraction and exponent to parse.
+        let is_integer_only = matches!(base, NumberBase::Binary | NumberBase::Octal);
+        // Base prefix was provided, but there were no digits after it, e.g. `0x`,
+        // so there is nothing more to parse unless a fractional part follows.
+        if !is_integer_only && (!empty_number || self.peek() == '.') {
+            if self.peek() == '.' {
+                self.consume();
+                is_float = true;
+                let has_fraction = match base {
+                    NumberBase::Decimal => self.consume_decimal_digits(),
+                    NumberBase::Hexadecimal => self.consume_hexadecimal_digits(),
+                    NumberBase::Binary | NumberBase::Octal => unreachable!(),
+                };
+                empty_number &= !has_fraction;
+            }
+            match (base, self.peek()) {
+                (NumberBase::Decimal, 'e' | 'E') | (NumberBase::Hexadecimal, 'p' | 'P') => {
+                    self.consume();
+                    is_float = true;
+                    has_exponent = true;
+                    let has_digits;
+                    (exponent_sign, has_digits) = self.consume_number_exponent();
+                    empty_exponent = !has_digits;
+                }
+                _ => (),
+            }
+        }
+
+        // A number can't be immediately followed by an identifier, e.g. `3x`,
+        // nor have a fractional part after the exponent, e.g. `3e2.5`,
+        // nor a binary or octal literal an out of range digit, e.g. `0o8`.
+        let malformed_suffix = self.is_ident_start(self.peek())
+            || (has_exponent && self.peek() == '.')
+            || (is_integer_only && self.peek().is_ascii_digit());
+
+        let misplaced_separator = self.options.digit_separators && {
+            let len = rest.len() - self.as_str().len();
+            has_misplaced_separator(&rest[..len], base)
         };
 
-        let empty_number = false;
+        Literal {
+            kind: Number {
+                base,
+                empty_number,
+                empty_exponent,
+                exponent_sign,
+                is_float,
+                leading_zero,
+                malformed_suffix,
+                misplaced_separator,
+            },
+        }
+    }
 
-        let kind = match self.peek() {
-            '.' => {
-                self.consume();
-                let mut empty_exponent = false;
-                match base {
-                    NumberBase::Decimal => {
-                        self.consume_decimal_digits();
-                        match self.peek() {
-                            'e' | 'E' => {
-                                self.consume();
-                                empty_exponent = !self.consume_number_exponent();
-                            }
-                            _ => (),
-                        }
-                    }
-                    NumberBase::Hexadecimal => {
-                        self.consume_hexadecimal_digits();
-                        match self.peek() {
-                            'p' | 'P' => {
-                                self.consume();
-                                empty_exponent = !self.consume_number_exponent();
-                            }
-                            _ => (),
-                        }
-                    }
+    /// Consumes an escape sequence right after the `\\`. Returns `false` if
+    /// the escape isn't recognized. `\z` is handled by `short_string`.
+    fn short_string_escape(&mut self) -> bool {
+        debug_assert!(self.prev() == '\\');
+        match self.consume() {
+            Some('a' | 'b' | 'f' | 'n' | 'r' | 't' | 'v' | '\\' | '"' | '\'') => true,
+            // Escaped line break, where `\r\n` and `\n\r` count as one.
+            Some(c @ ('\n' | '\r')) => {
+                let other = if c == '\n' { '\r' } else { '\n' };
+                self.consume_if(|c| c == other);
+                true
+            }
+            // Hexadecimal escape, `\xXX`, exactly two digits.
+            Some('x') => self.consume_n_if(2, |c| c.is_ascii_hexdigit()) == 2,
+            // UTF-8 escape, `\u{XXX}`, with a code point of at most 2^31 - 1.
+            Some('u') => {
+                if !self.consume_if(|c| c == '{') {
+                    return false;
                 }
-                Number {
-                    base,
-                    empty_exponent,
-                    empty_number,
+                let mut value = 0u32;
+                let mut has_digits = false;
+                while let Some(digit) = self.peek().to_digit(16) {
+                    has_digits = true;
+                    value = value.saturating_mul(16).saturating_add(digit);
+                    self.consume();
                 }
-            }
-            'e' | 'E' if base == NumberBase::Decimal => {
-                self.consume();
-                let empty_exponent = !self.consume_number_exponent();
-                Number {
-                    base,
-                    empty_exponent,
-                    empty_number,
+                if !self.consume_if(|c| c == '}') {
+                    return false;
                 }
+                has_digits && value <= 0x7FFF_FFFF
             }
-            'p' | 'P' if base == NumberBase::Hexadecimal => {
-                self.consume();
-                let empty_exponent = !self.consume_number_exponent();
-                Number {
-                    base,
-                    empty_exponent,
-                    empty_number,
+            // Decimal escape, `\ddd`, up to three digits and at most 255.
+            Some(c @ '0'..='9') => {
+                let mut value = c.to_digit(10).unwrap();
+                for _ in 0..2 {
+                    match self.peek().to_digit(10) {
+                        Some(digit) => {
+                            value = value * 10 + digit;
+                            self.consume();
+                        }
+                        None => break,
+                    }
                 }
+                value <= 255
             }
-            _ => Number {
-                base,
-                empty_exponent: false,
-                empty_number,
-            },
-        };
-        Literal { kind }
+            Some(_) => false,
+            // Backslash at the end of input, the string is unterminated anyway.
+            None => true,
+        }
     }
 
     fn short_string(&mut self, quote: char) -> TokenKind {
         debug_assert!(self.prev() == quote);
+        let mut has_invalid_escape = false;
+        let mut crossed_newline = false;
         let terminated = loop {
             match self.peek() {
                 c if c == quote => {
@@ -457,13 +1620,24 @@ impl Cursor<'_> {
                 }
                 '\\' => {
                     self.consume();
-                    // consume escaped character.
-                    if self.consume() == Some('z') {
+                    if self.peek() == 'z' {
+                        self.consume();
                         // consume whitespaces after `\z`.
-                        self.consume_while(is_whitespace);
+                        while is_whitespace(self.peek()) {
+                            if let Some('\n' | '\r') = self.consume() {
+                                crossed_newline = true;
+                            }
+                        }
+                    } else if !self.short_string_escape() {
+                        has_invalid_escape = true;
                     }
                 }
-                '\n' | EOF_CHAR => {
+                '\n' | '\r' => {
+                    break false;
+                }
+                // A NUL char can be a part of the string, `EOF_CHAR` is only
+                // the end of input if there is nothing left.
+                EOF_CHAR if self.is_eof() => {
                     break false;
                 }
                 _ => {
@@ -471,8 +1645,18 @@ impl Cursor<'_> {
                 }
             }
         };
+        let quote = if quote == '"' {
+            QuoteKind::Double
+        } else {
+            QuoteKind::Single
+        };
         Literal {
-            kind: ShortString { quote, terminated },
+            kind: ShortString {
+                quote,
+                terminated,
+                has_invalid_escape,
+                crossed_newline,
+            },
         }
     }
 
@@ -480,16 +1664,13 @@ impl Cursor<'_> {
         debug_assert!(self.prev() == '[');
         let mut terminated = false;
         while let Some(c) = self.consume() {
-            match c {
-                ']' => {
-                    let close_level = self.count_and_consume_while(|c| c == '=');
-                    if close_level == level && self.peek() == ']' {
-                        self.consume();
-                        terminated = true;
-                        break;
-                    }
+            if c == ']' {
+                let close_level = self.count_and_consume_while(|c| c == '=');
+                if close_level == level && self.peek() == ']' {
+                    self.consume();
+                    terminated = true;
+                    break;
                 }
-                _ => (),
             }
         }
         terminated
@@ -497,37 +1678,48 @@ impl Cursor<'_> {
 
     fn long_string(&mut self) -> TokenKind {
         debug_assert!(self.prev() == '[');
-        let kind = match self.peek() {
-            '[' => {
-                self.consume();
-                LongString {
-                    level: 0,
-                    terminated: self.consume_long_string_content(0),
-                }
-            }
-            '=' => {
-                let level = self.count_and_consume_while(|c| c == '=');
-                if self.peek() == '[' {
-                    self.consume();
-                    LongString {
-                        level,
-                        terminated: self.consume_long_string_content(level),
-                    }
-                } else {
-                    LongString {
-                        level,
-                        terminated: false,
-                    }
-                }
-            }
-            _ => unreachable!(),
-        };
-        Literal { kind }
+        debug_assert!(matches!(self.peek(), '[' | '='));
+        let level = self.count_and_consume_while(|c| c == '=');
+        let mut skipped_leading_newline = false;
+        let mut terminated = false;
+        if self.peek() == '[' {
+            self.consume();
+            skipped_leading_newline = matches!(self.peek(), '\n' | '\r');
+            terminated = self.consume_long_string_content(level);
+        }
+        Literal {
+            kind: LongString {
+                level,
+                terminated,
+                skipped_leading_newline,
+            },
+        }
     }
 
-    fn whitespace(&mut self) -> TokenKind {
+    fn whitespace(&mut self, first_char: char) -> TokenKind {
         debug_assert!(is_whitespace(self.prev()));
-        self.consume_while(is_whitespace);
-        Whitespace
+        let mut has_newline = false;
+        let mut has_unusual = false;
+        let mut c = first_char;
+        loop {
+            match c {
+                '\n' => has_newline = true,
+                '\r' => {
+                    has_newline = true;
+                    // A lone `\r` line break.
+                    has_unusual |= self.peek() != '\n';
+                }
+                '\u{000B}' | '\u{000C}' => has_unusual = true,
+                _ => (),
+            }
+            if !is_whitespace(self.peek()) {
+                break;
+            }
+            c = self.consume().unwrap();
+        }
+        Whitespace {
+            has_newline,
+            has_unusual,
+        }
     }
 }
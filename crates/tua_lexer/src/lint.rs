@@ -0,0 +1,89 @@
+//! Checks for likely mistakes and style issues in code that lexes fine.
+
+use alloc::vec::Vec;
+
+use crate::TokenKind::*;
+use crate::{tokenize_spanned, Span};
+
+/// Finds operators from C-like languages that aren't Lua operators: `&&`,
+/// `||`, `!=` and a leading `!`, which likely mean `and`, `or`, `~=` and
+/// `not`.
+///
+/// This doesn't change how they are lexed: `&` and `|` are `Unknown` tokens,
+/// and `!` is an identifier char, so `!x` is a single `Ident`. Single `&` and
+/// `|`, the Lua 5.3 bitwise operators, aren't reported.
+///
+/// A `#!` is reported as a whole unless it's at the start of the input, where
+/// it's a hashbang the caller didn't strip, see [`strip_hashbang`] and
+/// [`tokenize_with_shebang`]. Anywhere else it's likely a shebang line pasted
+/// in the middle of a file.
+///
+/// [`strip_hashbang`]: crate::strip_hashbang
+/// [`tokenize_with_shebang`]: crate::tokenize_with_shebang
+pub fn suspicious_operators(input: &str) -> Vec<Span> {
+    let text = |span: Span| &input[span.lo as usize..span.hi as usize];
+    let mut spans = Vec::new();
+    let mut prev = None;
+    let mut tokens = tokenize_spanned(input).peekable();
+    while let Some(token) = tokens.next() {
+        let span = token.span();
+        match token.kind {
+            Unknown if matches!(text(span), "&" | "|") => {
+                let doubled =
+                    tokens.next_if(|next| next.kind == Unknown && text(next.span()) == text(span));
+                if let Some(next) = doubled {
+                    spans.push(Span::new(span.lo, next.span().hi));
+                }
+            }
+            Ident => {
+                let ident = text(span);
+                let not_eq =
+                    ident.ends_with('!') && tokens.next_if(|next| next.kind == Eq).is_some();
+                if ident.starts_with('!') && !(not_eq && ident.len() == 1) {
+                    match prev {
+                        Some(Hash) if span.lo == 1 => (),
+                        Some(Hash) => spans.push(Span::new(span.lo - 1, span.lo + 1)),
+                        _ => spans.push(Span::new(span.lo, span.lo + 1)),
+                    }
+                }
+                if not_eq {
+                    spans.push(Span::new(span.hi - 1, span.hi + 1));
+                }
+            }
+            _ => (),
+        }
+        prev = Some(token.kind);
+    }
+    spans
+}
+
+/// Finds the indentation of lines mixing tabs and spaces, e.g. for a style
+/// check.
+///
+/// Only whitespace at the start of a line is considered, including blank
+/// lines, but not lines inside long strings and comments.
+pub fn mixed_indentation(input: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    for token in tokenize_spanned(input) {
+        let Whitespace { has_newline, .. } = token.kind else {
+            continue;
+        };
+        let at_line_start = token.start == 0;
+        if !has_newline && !at_line_start {
+            continue;
+        }
+        let span = token.span();
+        let mut lo = span.lo;
+        // Whitespace before the first line break ends the line of the
+        // previous token instead.
+        let mut skip = !at_line_start;
+        for line in input[span.lo as usize..span.hi as usize].split(['\n', '\r']) {
+            if !skip && line.contains(' ') && line.contains('\t') {
+                spans.push(Span::new(lo, lo + line.len() as u32));
+            }
+            skip = false;
+            lo += line.len() as u32 + 1;
+        }
+    }
+    spans
+}
@@ -0,0 +1,126 @@
+//! Operators and their precedence.
+
+use crate::TokenKind::{self, *};
+
+/// Binary operator, e.g. `+` or `and`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Or,
+    And,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Ne,
+    Eq,
+    BitXor,
+    Shl,
+    Shr,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    IDiv,
+    Mod,
+    Pow,
+}
+
+/// Unary operator, e.g. `-` or `not`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    Not,
+    Neg,
+    Len,
+    BitNot,
+}
+
+/// Associativity of a binary operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+/// Precedence of all unary operators. They bind tighter than any binary
+/// operator but `^`, so `-x ^ 2` is `-(x ^ 2)`.
+pub const UNARY_PRECEDENCE: u8 = 9;
+
+impl BinOp {
+    /// Returns the precedence of the operator, higher binding tighter, as
+    /// defined by the Lua reference manual, and its associativity.
+    pub fn precedence(self) -> (u8, Assoc) {
+        use BinOp::*;
+        match self {
+            Or => (1, Assoc::Left),
+            And => (2, Assoc::Left),
+            Lt | Gt | Le | Ge | Ne | Eq => (3, Assoc::Left),
+            BitXor => (4, Assoc::Left),
+            Shl | Shr => (5, Assoc::Left),
+            Concat => (6, Assoc::Right),
+            Add | Sub => (7, Assoc::Left),
+            Mul | Div | IDiv | Mod => (8, Assoc::Left),
+            Pow => (10, Assoc::Right),
+        }
+    }
+}
+
+/// Returns the binary operator for a punctuation token, with its precedence
+/// and associativity.
+///
+/// Multi-char operators, e.g. `..` or `==`, are only recognized once glued
+/// by [`glue_operators`](crate::glue_operators). The lexer doesn't produce
+/// tokens for the Lua 5.3 `&` and `|` operators, which lex as `Unknown`.
+/// `and` and `or` are identifiers, see [`keyword_binary_op`].
+pub fn binary_op(kind: TokenKind) -> Option<(BinOp, u8, Assoc)> {
+    let op = match kind {
+        Lt => BinOp::Lt,
+        Gt => BinOp::Gt,
+        LtEq => BinOp::Le,
+        GtEq => BinOp::Ge,
+        TildeEq => BinOp::Ne,
+        EqEq => BinOp::Eq,
+        Tilde => BinOp::BitXor,
+        LtLt => BinOp::Shl,
+        GtGt => BinOp::Shr,
+        DotDot => BinOp::Concat,
+        Plus => BinOp::Add,
+        Minus => BinOp::Sub,
+        Star => BinOp::Mul,
+        Slash => BinOp::Div,
+        SlashSlash => BinOp::IDiv,
+        Percent => BinOp::Mod,
+        Caret => BinOp::Pow,
+        _ => return None,
+    };
+    let (precedence, assoc) = op.precedence();
+    Some((op, precedence, assoc))
+}
+
+/// Returns the binary operator for the keyword `and` or `or`, with its
+/// precedence and associativity.
+pub fn keyword_binary_op(ident: &str) -> Option<(BinOp, u8, Assoc)> {
+    let op = match ident {
+        "and" => BinOp::And,
+        "or" => BinOp::Or,
+        _ => return None,
+    };
+    let (precedence, assoc) = op.precedence();
+    Some((op, precedence, assoc))
+}
+
+/// Returns the unary operator for a punctuation token. `not` is
+/// an identifier, see [`keyword_unary_op`].
+pub fn unary_op(kind: TokenKind) -> Option<UnOp> {
+    match kind {
+        Minus => Some(UnOp::Neg),
+        Hash => Some(UnOp::Len),
+        Tilde => Some(UnOp::BitNot),
+        _ => None,
+    }
+}
+
+/// Returns the unary operator for the keyword `not`.
+pub fn keyword_unary_op(ident: &str) -> Option<UnOp> {
+    (ident == "not").then_some(UnOp::Not)
+}
@@ -0,0 +1,426 @@
+//! Tokens with their positions in the source.
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::tokenize;
+use crate::{LiteralKind, QuoteKind, TokenKind};
+
+/// Range of bytes in the source, from `lo` inclusive to `hi` exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    pub lo: u32,
+    pub hi: u32,
+}
+
+impl Span {
+    pub fn new(lo: u32, hi: u32) -> Span {
+        debug_assert!(lo <= hi);
+        Span { lo, hi }
+    }
+
+    pub fn len(self) -> u32 {
+        self.hi - self.lo
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.lo == self.hi
+    }
+
+    pub fn with_lo(self, lo: u32) -> Span {
+        Span::new(lo, self.hi)
+    }
+
+    pub fn with_hi(self, hi: u32) -> Span {
+        Span::new(self.lo, hi)
+    }
+
+    /// Returns the empty span at the start of `self`.
+    pub fn shrink_to_lo(self) -> Span {
+        Span::new(self.lo, self.lo)
+    }
+
+    /// Returns the empty span at the end of `self`.
+    pub fn shrink_to_hi(self) -> Span {
+        Span::new(self.hi, self.hi)
+    }
+
+    /// Returns the span between the end of `self` and the start of `other`.
+    ///
+    /// If `other` starts before the end of `self`, the result is the empty
+    /// span at the end of `self`.
+    pub fn between(self, other: Span) -> Span {
+        Span::new(self.hi, other.lo.max(self.hi))
+    }
+}
+
+/// Token together with its byte offset in the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpannedToken {
+    pub kind: TokenKind,
+    pub start: u32,
+    pub len: u32,
+}
+
+impl SpannedToken {
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.start + self.len)
+    }
+}
+
+/// Creates an iterator that produces tokens from the input string,
+/// along with their byte offsets.
+pub fn tokenize_spanned(input: &str) -> impl Iterator<Item = SpannedToken> + '_ {
+    let mut start = 0;
+    tokenize(input).map(move |token| {
+        let spanned = SpannedToken {
+            kind: token.kind,
+            start,
+            len: token.len,
+        };
+        start += token.len;
+        spanned
+    })
+}
+
+/// Creates an iterator that produces the kinds and spans of tokens from
+/// the input string, with every span offset by `base`.
+///
+/// This is meant for sources laid out one after another in a single space of
+/// positions, where `base` is the position of the start of `input`.
+/// The lexer has no position types of its own besides [`Span`], so callers
+/// convert these spans to theirs.
+pub fn tokenize_with_base(input: &str, base: u32) -> impl Iterator<Item = (TokenKind, Span)> + '_ {
+    tokenize_spanned(input).map(move |token| {
+        let span = token.span();
+        (token.kind, Span::new(base + span.lo, base + span.hi))
+    })
+}
+
+/// Line of the input with its tokens, see [`tokenize_lines`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineTokens {
+    /// Number of the line, from 0.
+    pub line: usize,
+    /// Byte offset of the start of the line.
+    pub start: u32,
+    /// Token started on an earlier line that the line starts inside of, e.g.
+    /// a long comment. It covers the line from `start` up to its end.
+    pub continued: Option<SpannedToken>,
+    /// Tokens starting on the line.
+    pub tokens: Vec<SpannedToken>,
+}
+
+/// Creates an iterator over the lines of the input string, each with
+/// the tokens starting on it, e.g. for highlighting the source a line at
+/// a time.
+///
+/// Spans are relative to the start of the input. A token spanning several
+/// lines, e.g. a long comment or a whitespace token with a line break, is
+/// only in the `tokens` of the line it starts on, and is the `continued`
+/// token of the following lines it covers. A line inside such a token has
+/// no tokens at all. Line breaks are `\n`, `\r\n` and `\r`, and a line
+/// break at the end of the input doesn't start another line.
+pub fn tokenize_lines(input: &str) -> impl Iterator<Item = LineTokens> + '_ {
+    let ends_line = input.is_empty() || input.ends_with(['\n', '\r']);
+    let mut tokens = tokenize_spanned(input).peekable();
+    let mut line = 0;
+    // Line of the start of the next token, or of the end of the input.
+    let mut next_line = 0;
+    // Starts of the lines from `line` to `next_line`.
+    let mut line_starts = VecDeque::from([0]);
+    let mut last: Option<SpannedToken> = None;
+    core::iter::from_fn(move || {
+        if tokens.peek().is_none() && (line > next_line || line == next_line && ends_line) {
+            return None;
+        }
+        let start = line_starts.pop_front()?;
+        let continued = last.filter(|token| start < token.start + token.len);
+        let mut on_line = Vec::new();
+        while next_line == line {
+            let Some(token) = tokens.next() else {
+                break;
+            };
+            let span = token.span();
+            for end in line_break_ends(&input[span.lo as usize..span.hi as usize]) {
+                line_starts.push_back(span.lo + end as u32);
+                next_line += 1;
+            }
+            on_line.push(token);
+            last = Some(token);
+        }
+        line += 1;
+        Some(LineTokens {
+            line: line - 1,
+            start,
+            continued,
+            tokens: on_line,
+        })
+    })
+}
+
+/// Returns the offsets of the ends of the line breaks in `text`, where
+/// `\r\n` is a single one.
+fn line_break_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let bytes = text.as_bytes();
+    (0..bytes.len())
+        .filter(|&i| bytes[i] == b'\n' || bytes[i] == b'\r' && bytes.get(i + 1) != Some(&b'\n'))
+        .map(|i| i + 1)
+}
+
+/// Delimiters of a string literal, see [`string_literals`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringShape {
+    /// `'abc'` or `"abc"`.
+    Short { quote: QuoteKind },
+    /// `[[abc]]` or `[=[abc]=]`.
+    Long { level: usize },
+}
+
+/// Creates an iterator over the spans and shapes of the string literals in
+/// the input string, e.g. to find module names passed to `require`.
+pub fn string_literals(input: &str) -> impl Iterator<Item = (Span, StringShape)> + '_ {
+    tokenize_spanned(input).filter_map(|token| {
+        let shape = match token.kind {
+            TokenKind::Literal {
+                kind: LiteralKind::ShortString { quote, .. },
+            } => StringShape::Short { quote },
+            TokenKind::Literal {
+                kind: LiteralKind::LongString { level, .. },
+            } => StringShape::Long { level },
+            _ => return None,
+        };
+        Some((token.span(), shape))
+    })
+}
+
+/// Tokenizes the whole input string into a vector of tokens with their byte
+/// offsets, suitable for lookups with [`token_at`].
+pub fn tokenize_indexed(input: &str) -> Vec<SpannedToken> {
+    tokenize_spanned(input).collect()
+}
+
+/// Finds the token containing the byte offset `pos` in tokens sorted by
+/// their start, e.g. as returned by [`tokenize_indexed`].
+///
+/// Returns `None` if `pos` is past the end of the last token.
+pub fn token_at(tokens: &[SpannedToken], pos: u32) -> Option<&SpannedToken> {
+    let i = tokens
+        .partition_point(|token| token.start <= pos)
+        .checked_sub(1)?;
+    let token = &tokens[i];
+    (pos < token.start + token.len).then_some(token)
+}
+
+/// Tokens of a source, lexed once for random access, which borrow their
+/// text from the source.
+#[derive(Clone, Debug)]
+pub struct TokenStream<'a> {
+    src: &'a str,
+    tokens: Vec<SpannedToken>,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(src: &'a str) -> TokenStream<'a> {
+        TokenStream {
+            src,
+            tokens: tokenize_indexed(src),
+        }
+    }
+
+    pub fn src(&self) -> &'a str {
+        self.src
+    }
+
+    pub fn tokens(&self) -> &[SpannedToken] {
+        &self.tokens
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&SpannedToken> {
+        self.tokens.get(i)
+    }
+
+    /// Returns the source text of the `i`-th token.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn slice(&self, i: usize) -> &'a str {
+        let span = self.tokens[i].span();
+        &self.src[span.lo as usize..span.hi as usize]
+    }
+}
+
+impl core::ops::Index<usize> for TokenStream<'_> {
+    type Output = SpannedToken;
+
+    fn index(&self, i: usize) -> &SpannedToken {
+        &self.tokens[i]
+    }
+}
+
+/// Side to prefer when a position is on the boundary of two tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bias {
+    /// Prefer the token ending at the position.
+    Left,
+    /// Prefer the token starting at the position.
+    Right,
+}
+
+/// Finds the index of the token containing the byte offset `pos` in tokens
+/// sorted by their start, e.g. as returned by [`tokenize_indexed`].
+///
+/// Unlike [`token_at`], the end of a token counts as a part of it, so when
+/// `pos` is between two tokens `bias` selects one of them. If there is
+/// a token on one side only, e.g. at the end of the input, it's returned
+/// regardless of `bias`. Returns `None` if `pos` is past the end of the last
+/// token.
+pub fn token_containing(tokens: &[SpannedToken], pos: u32, bias: Bias) -> Option<usize> {
+    let i = tokens
+        .partition_point(|token| token.start <= pos)
+        .checked_sub(1)?;
+    let token = &tokens[i];
+    if pos > token.start + token.len {
+        return None;
+    }
+    let ends_previous = i > 0 && pos == tokens[i - 1].start + tokens[i - 1].len;
+    if bias == Bias::Left && pos == token.start && ends_previous {
+        return Some(i - 1);
+    }
+    Some(i)
+}
+
+/// First flaw found by [`verify_coverage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoverageError {
+    /// No token covers the bytes `span`.
+    Gap { span: Span },
+    /// The bytes `span` are covered by more than one token.
+    Overlap { span: Span },
+    /// The token `span` ends past the end of the source.
+    OutOfBounds { span: Span },
+    /// A token starts or ends at the byte offset `pos`, which isn't a char
+    /// boundary.
+    NotCharBoundary { pos: u32 },
+}
+
+/// Checks that tokens sorted by their start cover the whole source, one
+/// after another, e.g. as a debug assertion on the output of a tokenizer.
+pub fn verify_coverage(src: &str, tokens: &[SpannedToken]) -> Result<(), CoverageError> {
+    let mut pos = 0;
+    for token in tokens {
+        let span = token.span();
+        if span.lo > pos {
+            return Err(CoverageError::Gap {
+                span: Span::new(pos, span.lo),
+            });
+        }
+        if span.lo < pos {
+            return Err(CoverageError::Overlap {
+                span: Span::new(span.lo, pos.min(span.hi)),
+            });
+        }
+        if span.hi as usize > src.len() {
+            return Err(CoverageError::OutOfBounds { span });
+        }
+        if !src.is_char_boundary(span.hi as usize) {
+            return Err(CoverageError::NotCharBoundary { pos: span.hi });
+        }
+        pos = span.hi;
+    }
+    if (pos as usize) < src.len() {
+        return Err(CoverageError::Gap {
+            span: Span::new(pos, src.len() as u32),
+        });
+    }
+    Ok(())
+}
+
+/// Renders the tokens of the input string one per line, with their kind,
+/// byte range and source text, e.g. `Ident@0..3 "foo"`.
+///
+/// Literal tokens are shown by the kind of the literal, e.g. `Number`, and
+/// flags of the kinds are left out. This is the recommended way to snapshot
+/// the lexer output in tests, as it keeps diffs small and readable.
+pub fn dump_tokens_compact(input: &str) -> String {
+    let mut out = String::new();
+    for token in tokenize_spanned(input) {
+        let kind = match token.kind {
+            TokenKind::Literal { kind } => format!("{:?}", kind),
+            kind => format!("{:?}", kind),
+        };
+        let name = kind.split(' ').next().unwrap_or_default();
+        let span = token.span();
+        let text = &input[span.lo as usize..span.hi as usize];
+        // Writing to a `String` can't fail.
+        let _ = writeln!(out, "{}@{}..{} {:?}", name, span.lo, span.hi, text);
+    }
+    out
+}
+
+/// Updates the tokens of a source after an edit, lexing only the affected
+/// region.
+///
+/// `old_tokens` are the tokens of the source before the edit, `edit` is the
+/// replaced range in the old source, and `new_len` is the length of
+/// the replacement text. `src` is the source after the edit.
+///
+/// Lexing restarts at the token boundary before the edit, and stops as soon
+/// as a new token starts where an old one did, past the edit. If the edit
+/// changes how the rest of the source is lexed, e.g. by opening a long
+/// comment, everything up to the end of the source is lexed again.
+pub fn relex(
+    old_tokens: &[SpannedToken],
+    src: &str,
+    edit: Span,
+    new_len: u32,
+) -> Vec<SpannedToken> {
+    // The lexer looks up to two chars past the end of a token, e.g. at `+1`
+    // after `3e`, so the two tokens before the edit may change too. Restart
+    // from the second to last token starting before the edit.
+    let restart = old_tokens
+        .partition_point(|token| token.start < edit.lo)
+        .saturating_sub(2);
+    let restart_pos = old_tokens.get(restart).map_or(0, |token| token.start);
+    let edit_end = edit.lo + new_len;
+    let delta = i64::from(new_len) - i64::from(edit.len());
+    let shift = |token: &SpannedToken| SpannedToken {
+        start: (i64::from(token.start) + delta) as u32,
+        ..*token
+    };
+
+    let mut tokens = old_tokens[..restart].to_vec();
+    let mut pos = restart_pos;
+    for token in tokenize(&src[restart_pos as usize..]) {
+        if pos >= edit_end {
+            let old_pos = (i64::from(pos) - delta) as u32;
+            let old_rest = &old_tokens[restart..];
+            if let Ok(i) = old_rest.binary_search_by_key(&old_pos, |token| token.start) {
+                let old = &old_rest[i];
+                if old.kind == token.kind && old.len == token.len {
+                    tokens.extend(old_rest[i..].iter().map(shift));
+                    return tokens;
+                }
+            }
+        }
+        tokens.push(SpannedToken {
+            kind: token.kind,
+            start: pos,
+            len: token.len,
+        });
+        pos += token.len;
+    }
+    tokens
+}
@@ -14,6 +14,35 @@ fn hashbang_second_line() {
     assert_eq!(strip_hashbang(input), None);
 }
 
+#[test]
+fn bom_only() {
+    assert_eq!(strip_bom("\u{FEFF}"), Some(3));
+}
+
+#[test]
+fn bom_then_hashbang() {
+    let input = "\u{FEFF}#!/usr/bin/env tua\nlocal x = 0;";
+    let bom_len = strip_bom(input).unwrap();
+    assert_eq!(bom_len, 3);
+    assert_eq!(strip_hashbang(&input[bom_len..]), Some(18));
+}
+
+#[test]
+fn no_bom() {
+    assert_eq!(strip_bom("local x = 0;"), None);
+    assert_eq!(strip_bom("#!/usr/bin/env tua\n\u{FEFF}"), None);
+}
+
+#[test]
+fn line_endings() {
+    assert_eq!(detect_line_ending(""), LineEnding::Lf);
+    assert_eq!(detect_line_ending("a\nb\n"), LineEnding::Lf);
+    assert_eq!(detect_line_ending("a\r\nb\r\n"), LineEnding::CrLf);
+    assert_eq!(detect_line_ending("a\rb\r"), LineEnding::Cr);
+    assert_eq!(detect_line_ending("a\r\nb\n"), LineEnding::Mixed);
+    assert_eq!(detect_line_ending("a\rb\r\n"), LineEnding::Mixed);
+}
+
 fn check_lexing(src: &str, expect: Expect) {
     let actual: String = tokenize(src)
         .map(|token| format!("{:?}\n", token))
@@ -21,10 +50,14 @@ fn check_lexing(src: &str, expect: Expect) {
     expect.assert_eq(&actual)
 }
 
-#[test]
-fn smoke_test() {
-    check_lexing(
-        r#"
+fn check_lexing_with(src: &str, options: LexOptions, expect: Expect) {
+    let actual: String = tokenize_with(src, options)
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect.assert_eq(&actual)
+}
+
+const SMOKE_TEST_SRC: &str = r#"
 --[[ comment ]]
 function fn() {
   print("");
@@ -35,34 +68,39 @@ local x, y = 0 + 1 - 2 * 3 / 4 ^ 5 % 6, 0
 if x ~= y or y <= z or z > x then
   goto ::label::
 end
-"#,
+"#;
+
+#[test]
+fn smoke_test() {
+    check_lexing(
+        SMOKE_TEST_SRC,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: LongComment { terminated: true }, len: 15 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: LongComment { level: 0, terminated: true }, len: 15 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 8 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 2 }
             Token { kind: OpenParen, len: 1 }
             Token { kind: CloseParen, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: OpenBrace, len: 1 }
-            Token { kind: Whitespace, len: 3 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 3 }
             Token { kind: Ident, len: 5 }
             Token { kind: OpenParen, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '"', terminated: true } }, len: 2 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 2 }
             Token { kind: CloseParen, len: 1 }
             Token { kind: Semi, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: CloseBrace, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Hash, len: 1 }
             Token { kind: Ident, len: 1 }
             Token { kind: OpenBracket, len: 1 }
             Token { kind: Ident, len: 1 }
             Token { kind: CloseBracket, len: 1 }
             Token { kind: OpenBracket, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '\'', terminated: true } }, len: 3 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 3 }
             Token { kind: CloseBracket, len: 1 }
             Token { kind: Colon, len: 1 }
             Token { kind: Ident, len: 1 }
@@ -71,89 +109,89 @@ end
             Token { kind: Dot, len: 1 }
             Token { kind: Dot, len: 1 }
             Token { kind: CloseParen, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Colon, len: 1 }
             Token { kind: Colon, len: 1 }
             Token { kind: Ident, len: 5 }
             Token { kind: Colon, len: 1 }
             Token { kind: Colon, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 5 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
             Token { kind: Comma, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Eq, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Plus, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Minus, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Star, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Slash, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Caret, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Percent, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
             Token { kind: Comma, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 2 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Tilde, len: 1 }
             Token { kind: Eq, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 2 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Lt, len: 1 }
             Token { kind: Eq, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 2 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Gt, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 4 }
-            Token { kind: Whitespace, len: 3 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 3 }
             Token { kind: Ident, len: 4 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
             Token { kind: Colon, len: 1 }
             Token { kind: Colon, len: 1 }
             Token { kind: Ident, len: 5 }
             Token { kind: Colon, len: 1 }
             Token { kind: Colon, len: 1 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: Ident, len: 3 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -168,15 +206,15 @@ fn short_comment() {
 ---[[ still short ]]
 ",
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: ShortComment, len: 2 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: ShortComment, len: 8 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: ShortComment, len: 10 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
             Token { kind: ShortComment, len: 20 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -192,11 +230,11 @@ fn long_comment() {
 --]=]
 ",
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: LongComment { terminated: true }, len: 6 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: LongComment { terminated: true }, len: 34 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: LongComment { level: 0, terminated: true }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: LongComment { level: 1, terminated: true }, len: 34 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -208,8 +246,8 @@ fn unterminated_long_comment() {
 --[=[ ]]
 ",
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: LongComment { terminated: false }, len: 9 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: LongComment { level: 1, terminated: false }, len: 9 }
         "#]],
     )
 }
@@ -222,11 +260,11 @@ fn short_string() {
 "string"
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '\'', terminated: true } }, len: 7 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '"', terminated: true } }, len: 8 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 8 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -239,11 +277,11 @@ fn unterminated_short_string() {
 "string'
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '\'', terminated: false } }, len: 7 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: ShortString { quote: '"', terminated: false } }, len: 8 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 8 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -258,9 +296,9 @@ fn long_string() {
 ]==]
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: LongString { level: 2, terminated: true } }, len: 31 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 2, terminated: true, skipped_leading_newline: true } }, len: 31 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -275,8 +313,8 @@ fn unterminated_long_string() {
 ]=]
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: LongString { level: 2, terminated: false } }, len: 31 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 2, terminated: false, skipped_leading_newline: true } }, len: 31 }
         "#]],
     )
 }
@@ -293,19 +331,19 @@ fn decimal_number() {
 0.31416E1
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 1 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 3 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 6 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 9 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true } }, len: 7 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false } }, len: 9 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 3 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: Some('-'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 9 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 9 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
@@ -322,19 +360,1969 @@ fn hexadecimal_number() {
 0X1.921FB54442D18P+1
 "#,
         expect![[r#"
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: true, empty_exponent: false } }, len: 2 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false } }, len: 4 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false } }, len: 6 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false } }, len: 8 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: true } }, len: 7 }
-            Token { kind: Whitespace, len: 1 }
-            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false } }, len: 20 }
-            Token { kind: Whitespace, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: true, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: Some('-'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 8 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Minus, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: Some('+'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 20 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn hexadecimal_int_and_float() {
+    check_lexing(
+        r#"
+0x10
+0x10p0
+0x1.8
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn short_string_escapes() {
+    check_lexing(
+        r#"
+"\q"
+"\xGG"
+"\n\t"
+"\65\255\256"
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 13 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn short_string_hex_and_utf8_escapes() {
+    check_lexing(
+        r#"
+"\x41\xff"
+"\xZZ"
+"\x4"
+"\u{48}\u{1F600}"
+"\u{110000}"
+"\u{}"
+"\u{41"
+"\u41"
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 10 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 17 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 12 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: true, crossed_newline: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn tokenize_from_token_boundary() {
+    let input = "local x = 'a'";
+    let tokens: Vec<_> = tokenize_from(input, 10).collect();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].len, 3);
+    assert!(matches!(
+        tokens[0].kind,
+        Literal {
+            kind: ShortString {
+                terminated: true,
+                ..
+            }
+        }
+    ));
+}
+
+#[test]
+fn tokenize_from_non_boundary() {
+    let input = "local é = 0";
+    // Inside the two-byte `é`.
+    assert_eq!(tokenize_from(input, 7).count(), 0);
+    // Out of range.
+    assert_eq!(tokenize_from(input, 100).count(), 0);
+    // At the end of input.
+    assert_eq!(tokenize_from(input, input.len()).count(), 0);
+}
+
+#[test]
+fn significant_tokens() {
+    let src = "local x = 1 -- one\n--[[ two ]] f(x)\n";
+    let actual: String = tokenize_significant(src)
+        .map(|token| {
+            let span = token.span();
+            format!(
+                "{:?} {:?}\n",
+                span,
+                &src[span.lo as usize..span.hi as usize]
+            )
+        })
+        .collect();
+    expect![[r#"
+        Span { lo: 0, hi: 5 } "local"
+        Span { lo: 6, hi: 7 } "x"
+        Span { lo: 8, hi: 9 } "="
+        Span { lo: 10, hi: 11 } "1"
+        Span { lo: 31, hi: 32 } "f"
+        Span { lo: 32, hi: 33 } "("
+        Span { lo: 33, hi: 34 } "x"
+        Span { lo: 34, hi: 35 } ")"
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn malformed_long_string_opening() {
+    check_lexing(
+        "[=",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 1, terminated: false, skipped_leading_newline: false } }, len: 2 }
+        "#]],
+    );
+    check_lexing(
+        "[==",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 2, terminated: false, skipped_leading_newline: false } }, len: 3 }
+        "#]],
+    );
+    check_lexing(
+        "[=x",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 1, terminated: false, skipped_leading_newline: false } }, len: 2 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn coalesced_unknown() {
+    let actual: String = coalesce_unknown(tokenize("&&a||"))
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect![[r#"
+        Token { kind: Unknown, len: 2 }
+        Token { kind: Ident, len: 1 }
+        Token { kind: Unknown, len: 2 }
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn leading_zero_number() {
+    check_lexing(
+        r#"
+0755
+0
+0.5
+0x10
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: true, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 3 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn attribute_names() {
+    assert!(is_attribute_name("const"));
+    assert!(is_attribute_name("close"));
+    assert!(!is_attribute_name("readonly"));
+}
+
+#[test]
+fn display_token_kind() {
+    let actual: String = tokenize("--x\nfoo(1, 'a') + [[b]]")
+        .map(|token| format!("{}\n", token.kind))
+        .collect();
+    expect![[r#"
+        short comment
+        whitespace
+        identifier
+        (
+        number literal
+        ,
+        whitespace
+        string literal
+        )
+        whitespace
+        +
+        whitespace
+        long string literal
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn lexed_slices() {
+    let src = "local café = 'π' --[[ ü ]]";
+    let lexed = Lexed::new(src);
+    let actual: String = lexed
+        .iter()
+        .map(|(kind, text)| format!("{:?} {:?}\n", kind, text))
+        .collect();
+    expect![[r#"
+        Ident "local"
+        Whitespace { has_newline: false, has_unusual: false } " "
+        Ident "café"
+        Whitespace { has_newline: false, has_unusual: false } " "
+        Eq "="
+        Whitespace { has_newline: false, has_unusual: false } " "
+        Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } } "'π'"
+        Whitespace { has_newline: false, has_unusual: false } " "
+        LongComment { level: 0, terminated: true } "--[[ ü ]]"
+    "#]].assert_eq(&actual);
+    let reconstructed: String = lexed.iter().map(|(_, text)| text).collect();
+    assert_eq!(reconstructed, src);
+    let mut reconstructed = String::new();
+    for (_, text) in &lexed {
+        reconstructed.push_str(text);
+    }
+    assert_eq!(reconstructed, src);
+    let reconstructed: String = Lexed::new(SMOKE_TEST_SRC)
+        .iter()
+        .map(|(_, text)| text)
+        .collect();
+    assert_eq!(reconstructed, SMOKE_TEST_SRC);
+}
+
+#[test]
+fn short_string_skip_whitespace_escape() {
+    check_lexing(
+        "\"a\\z  b\"",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 8 }
+        "#]],
+    );
+    check_lexing(
+        "\"a\\z\n b\"",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: true } }, len: 8 }
+        "#]],
+    );
+    check_lexing(
+        "\"a\\z\n\n  local x = 1",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: false, has_invalid_escape: false, crossed_newline: true } }, len: 19 }
+        "#]],
+    );
+}
+
+#[test]
+fn tokenize_chars_matches_tokenize() {
+    let long_string = format!("[==[{}]==] x", "]=] ".repeat(100));
+    // The exponent digit is past the first chunk of chars read.
+    let exponent = format!("{}3e+1", " ".repeat(61));
+    for src in [
+        SMOKE_TEST_SRC,
+        "",
+        "x",
+        "local s = 'abc",
+        long_string.as_str(),
+        "--[[ unterminated",
+        exponent.as_str(),
+    ] {
+        let expected: Vec<_> = tokenize(src).collect();
+        let actual: Vec<_> = tokenize_chars(src.chars()).collect();
+        assert_eq!(actual, expected, "{:?}", src);
+    }
+}
+
+#[test]
+fn tokenize_chars_large_input() {
+    // A huge token grows the buffer, which used to make lexing the many small
+    // tokens after it quadratic.
+    let src = format!("[[{}]] {}", "x".repeat(512 * 1024), "a ".repeat(1 << 19));
+    let expected: Vec<_> = tokenize(&src).collect();
+    let actual: Vec<_> = tokenize_chars(src.chars()).collect();
+    assert_eq!(actual, expected);
+    let actual: Vec<_> = tokenize_bytes(src.as_bytes()).collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn number_empty_parts() {
+    check_lexing(
+        r#"
+0x.p2
+0x.8
+0x1.8p
+3e
+.e5
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: true, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Dot, len: 1 }
+            Token { kind: Ident, len: 2 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        "#]],
+    )
+}
+
+#[test]
+fn tokenize_to_vec_matches_tokenize() {
+    let expected: Vec<_> = tokenize(SMOKE_TEST_SRC).collect();
+    assert_eq!(tokenize_to_vec(SMOKE_TEST_SRC), expected);
+    assert!(tokenize_to_vec("").is_empty());
+}
+
+#[test]
+fn non_ascii_ident() {
+    check_lexing(
+        "café _x1 π",
+        expect![[r#"
+            Token { kind: Ident, len: 5 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 2 }
+        "#]],
+    );
+}
+
+#[test]
+fn non_ascii_ident_strict() {
+    let options = LexOptions {
+        strict_idents: true,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "café _x1 π",
+        options,
+        expect![[r#"
+            Token { kind: Ident, len: 3 }
+            Token { kind: Unknown, len: 2 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Unknown, len: 2 }
+        "#]],
+    );
+}
+
+#[test]
+fn floor_division() {
+    let lua53 = LexOptions {
+        lua_version: LuaVersion::Lua53,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "a//b",
+        lua53,
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: SlashSlash, len: 2 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    let lua51 = LexOptions {
+        lua_version: LuaVersion::Lua51,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "a//b",
+        lua51,
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Slash, len: 1 }
+            Token { kind: Slash, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn number_with_suffix() {
+    check_lexing(
+        r#"
+3x
+0x1g
+3.5f
+3 x
+"#,
+        expect![[r#"
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 3 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 3 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
         "#]],
     )
 }
+
+fn check_normalized_colons(src: &str, expect: Expect) {
+    let actual: String = normalize_colons(tokenize(src))
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect.assert_eq(&actual)
+}
+
+#[test]
+fn normalized_colons() {
+    check_normalized_colons(
+        "a:b",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Colon, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_normalized_colons(
+        "::x::",
+        expect![[r#"
+            Token { kind: ColonColon, len: 2 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: ColonColon, len: 2 }
+        "#]],
+    );
+    check_normalized_colons(
+        "a::b",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: ColonColon, len: 2 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_normalized_colons(
+        ":::",
+        expect![[r#"
+            Token { kind: ColonColon, len: 2 }
+            Token { kind: Colon, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn short_string_with_long_brackets() {
+    check_lexing(
+        r#"'[[ a ]]' "]=]""#,
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 9 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 5 }
+        "#]],
+    );
+}
+
+#[test]
+fn short_string_backslash_at_eof() {
+    check_lexing(
+        r#""abc\"#,
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 5 }
+        "#]],
+    );
+}
+
+#[test]
+fn token_equality() {
+    let tokens: Vec<_> = tokenize(" x=1").collect();
+    assert_eq!(
+        tokens[0],
+        Token {
+            kind: Whitespace {
+                has_newline: false,
+                has_unusual: false
+            },
+            len: 1
+        }
+    );
+    assert_eq!(
+        tokens[1],
+        Token {
+            kind: Ident,
+            len: 1
+        }
+    );
+    assert_eq!(tokens[2], Token { kind: Eq, len: 1 });
+    assert_ne!(
+        tokens[1],
+        Token {
+            kind: Ident,
+            len: 2
+        }
+    );
+}
+
+#[test]
+fn tokenize_consumes_all_input() {
+    fn check(src: &str) {
+        let mut total = 0;
+        for token in tokenize(src) {
+            assert!(token.len > 0, "empty token in {:?}", src);
+            total += token.len as usize;
+        }
+        assert_eq!(total, src.len(), "{:?}", src);
+    }
+
+    for src in [
+        SMOKE_TEST_SRC,
+        "'unterminated",
+        "\"\\",
+        "\\",
+        "[=",
+        "[==[ ]=] ]",
+        "--[=",
+        "--[==[ ]=]",
+        "3e+-+-+-",
+        "0x.p+",
+        "\"\\u{",
+        "\"\\z",
+        "\0\0",
+    ] {
+        check(src);
+    }
+
+    // Every combination of up to three chars from a set of tricky ones.
+    let chars = [
+        '-', '[', '=', ']', '"', '\'', '\\', 'z', 'u', '{', '\n', '\r', '0', 'x', '.', 'e', 'p',
+        '+', ' ', 'é', '\0',
+    ];
+    let mut src = String::new();
+    for &a in &chars {
+        for &b in &chars {
+            for &c in &chars {
+                src.clear();
+                src.extend([a, b, c]);
+                for end in 1..=src.chars().count() {
+                    let end = src.char_indices().nth(end).map_or(src.len(), |(i, _)| i);
+                    check(&src[..end]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn short_comment_at_eof() {
+    check_lexing(
+        "-- eof",
+        expect![[r#"
+            Token { kind: ShortComment, len: 6 }
+        "#]],
+    );
+    check_lexing(
+        "--",
+        expect![[r#"
+            Token { kind: ShortComment, len: 2 }
+        "#]],
+    );
+    check_lexing(
+        "--\r\n",
+        expect![[r#"
+            Token { kind: ShortComment, len: 2 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 2 }
+        "#]],
+    );
+    check_lexing(
+        "-- a\rb",
+        expect![[r#"
+            Token { kind: ShortComment, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: true }, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+}
+
+fn check_relex(old_src: &str, edit: Span, replacement: &str) {
+    let old_tokens: Vec<_> = tokenize_spanned(old_src).collect();
+    let mut new_src = old_src.to_owned();
+    new_src.replace_range(edit.lo as usize..edit.hi as usize, replacement);
+    let actual: Vec<_> = relex(&old_tokens, &new_src, edit, replacement.len() as u32)
+        .into_iter()
+        .map(|t| (t.kind, t.start, t.len))
+        .collect();
+    let expected: Vec<_> = tokenize_spanned(&new_src)
+        .map(|t| (t.kind, t.start, t.len))
+        .collect();
+    assert_eq!(actual, expected, "{:?}", new_src);
+}
+
+#[test]
+fn relex_in_word_edit() {
+    check_relex("local foo = 1", Span::new(7, 8), "xyz");
+    check_relex("local foo = 1", Span::new(6, 9), "");
+    check_relex("local foo = 1", Span::new(9, 9), "bar");
+    check_relex("a -b", Span::new(3, 3), "-");
+    check_relex(SMOKE_TEST_SRC, Span::new(50, 52), "+");
+    check_relex("x = 3e+ y", Span::new(7, 7), "5");
+}
+
+#[test]
+fn relex_edit_opening_long_comment() {
+    check_relex("a = 1\nb = 2\nc = 3", Span::new(6, 6), "--[[");
+    check_relex("--[[ a ]] b = 2", Span::new(0, 1), "");
+}
+
+#[test]
+fn lexer_pause_and_resume() {
+    let mut lexer = Lexer::new("f(`a ${b}`) + 1");
+    assert_eq!(
+        lexer.next_token(),
+        Some(Token {
+            kind: Ident,
+            len: 1
+        })
+    );
+    assert_eq!(
+        lexer.next_token(),
+        Some(Token {
+            kind: OpenParen,
+            len: 1
+        })
+    );
+    assert_eq!(lexer.remaining(), "`a ${b}`) + 1");
+    // Scan the template string with custom logic.
+    let template_len = lexer.remaining()[1..].find('`').unwrap() + 2;
+    lexer.skip(template_len);
+    assert_eq!(lexer.remaining(), ") + 1");
+    assert_eq!(
+        lexer.next_token(),
+        Some(Token {
+            kind: CloseParen,
+            len: 1
+        })
+    );
+    assert_eq!(std::iter::from_fn(|| lexer.next_token()).count(), 4);
+    assert_eq!(lexer.remaining(), "");
+}
+
+#[test]
+fn unusual_whitespace() {
+    check_lexing(
+        "a  \tb",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 3 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "a \x0C b",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: true }, len: 3 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "a\x0Bb",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: true }, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "a\r\nb",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 2 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "a\rb",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: true }, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "a\r",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: true }, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn long_bracket_contents() {
+    assert_eq!(long_bracket_content("[[abc]]", 0), "abc");
+    assert_eq!(long_bracket_content("[==[a]]b]=]c]==]", 2), "a]]b]=]c");
+    assert_eq!(long_bracket_content("[[]]", 0), "");
+    assert_eq!(long_bracket_content("[[\nabc\n]]", 0), "abc\n");
+    assert_eq!(long_bracket_content("[=[\r\n\nabc]=]", 1), "\nabc");
+    assert_eq!(long_bracket_content("[==[abc", 2), "abc");
+    assert_eq!(long_bracket_content("[==[abc]=]", 2), "abc]=]");
+    assert_eq!(long_bracket_content("[[\nabc]", 0), "abc]");
+
+    let src = "--[=[\ncomment]=]";
+    let token = tokenize(src).next().unwrap();
+    let LongComment { level, .. } = token.kind else {
+        panic!("{:?}", token.kind)
+    };
+    assert_eq!(long_bracket_content(&src[2..], level), "comment");
+}
+
+#[test]
+fn smoke_test_stats() {
+    let stats = token_stats(SMOKE_TEST_SRC);
+    assert_eq!(stats.idents, 23);
+    assert_eq!(stats.numbers, 8);
+    assert_eq!(stats.number_bytes, 8);
+    assert_eq!(stats.comments, 1);
+    assert_eq!(stats.comment_bytes, 15);
+    assert_eq!(stats.strings, 2);
+}
+
+#[test]
+fn binary_octal_literals() {
+    let options = LexOptions {
+        binary_octal_literals: true,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "0b1010 0B 0o777 0o8 0b12 0o7.5",
+        options,
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Binary, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Binary, empty_number: true, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Octal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Octal, empty_number: true, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 2 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Binary, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 3 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Octal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 3 }
+            Token { kind: Dot, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn binary_octal_literals_disabled() {
+    check_lexing(
+        "0b1010 0o7",
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 1 }
+            Token { kind: Ident, len: 5 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 1 }
+            Token { kind: Ident, len: 2 }
+        "#]],
+    );
+}
+
+#[test]
+fn leading_trivia() {
+    let actual: String = tokenize_with_trivia("x = 1\n-- doc\nfunction f() end\n")
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect![[r#"
+        TokenWithTrivia { leading: [], token: Some(Token { kind: Ident, len: 1 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }], token: Some(Token { kind: Eq, len: 1 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }], token: Some(Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }, Token { kind: ShortComment, len: 6 }, Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }], token: Some(Token { kind: Ident, len: 8 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }], token: Some(Token { kind: Ident, len: 1 }) }
+        TokenWithTrivia { leading: [], token: Some(Token { kind: OpenParen, len: 1 }) }
+        TokenWithTrivia { leading: [], token: Some(Token { kind: CloseParen, len: 1 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }], token: Some(Token { kind: Ident, len: 3 }) }
+        TokenWithTrivia { leading: [Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }], token: None }
+    "#]].assert_eq(&actual);
+}
+
+#[test]
+fn trailing_trivia() {
+    for src in ["x -- trailing comment\n", "x  ", "x", "", " \n"] {
+        let tokens: Vec<_> = tokenize_with_trivia(src).collect();
+        let len: u32 = tokens
+            .iter()
+            .flat_map(|token| token.leading.iter().chain(&token.token))
+            .map(|token| token.len)
+            .sum();
+        assert_eq!(len as usize, src.len(), "{:?}", src);
+    }
+
+    let tokens: Vec<_> = tokenize_with_trivia("x -- trailing comment\n").collect();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[1].token, None);
+    let kinds: Vec<_> = tokens[1].leading.iter().map(|token| token.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            Whitespace {
+                has_newline: false,
+                has_unusual: false
+            },
+            ShortComment,
+            Whitespace {
+                has_newline: true,
+                has_unusual: false
+            },
+        ]
+    );
+    assert_eq!(tokenize_with_trivia("").count(), 0);
+}
+
+#[test]
+fn lossy_bytes() {
+    // `\xC3` starts a two byte sequence, but `(` isn't a continuation byte.
+    let input = b"f\xC3(x) \xFF\xFE";
+    let decoded = String::from_utf8_lossy(input);
+    assert_eq!(decoded, "f\u{FFFD}(x) \u{FFFD}\u{FFFD}");
+    let actual: String = tokenize_bytes(input)
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect![[r#"
+        Token { kind: Ident, len: 4 }
+        Token { kind: OpenParen, len: 1 }
+        Token { kind: Ident, len: 1 }
+        Token { kind: CloseParen, len: 1 }
+        Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+        Token { kind: Ident, len: 6 }
+    "#]]
+    .assert_eq(&actual);
+    let total: u32 = tokenize_bytes(input).map(|token| token.len).sum();
+    assert_eq!(total as usize, decoded.len());
+}
+
+#[test]
+fn shebang_token() {
+    let actual: String = tokenize_with_shebang("#!/usr/bin/env tua\nx")
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect![[r#"
+        Token { kind: Shebang, len: 18 }
+        Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+        Token { kind: Ident, len: 1 }
+    "#]]
+    .assert_eq(&actual);
+    let actual: String = tokenize_with_shebang("#x")
+        .map(|token| format!("{:?}\n", token))
+        .collect();
+    expect![[r#"
+        Token { kind: Hash, len: 1 }
+        Token { kind: Ident, len: 1 }
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn exponent_sign() {
+    check_lexing(
+        "3e+5 3e-5 3e5 0x1P-4 3",
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: Some('+'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: Some('-'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 3 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: Some('-'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 6 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn token_at_offset() {
+    let src = "x = [[ab]]";
+    let tokens = tokenize_indexed(src);
+    let kind_at = |pos| token_at(&tokens, pos).map(|token| token.kind.to_string());
+    assert_eq!(kind_at(0).as_deref(), Some("identifier"));
+    assert_eq!(kind_at(1).as_deref(), Some("whitespace"));
+    assert_eq!(kind_at(2).as_deref(), Some("="));
+    assert_eq!(kind_at(4).as_deref(), Some("long string literal"));
+    assert_eq!(kind_at(7).as_deref(), Some("long string literal"));
+    assert_eq!(kind_at(9).as_deref(), Some("long string literal"));
+    assert_eq!(kind_at(10), None);
+    assert_eq!(token_at(&tokens, 6).unwrap().span(), Span::new(4, 10));
+    assert!(token_at(&[], 0).is_none());
+}
+
+#[test]
+fn dot_after_exponent() {
+    check_lexing(
+        "0x1p2.5 3e2.5 0x1.8p2 3.5",
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 5 }
+            Token { kind: Dot, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 3 }
+            Token { kind: Dot, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 3 }
+        "#]],
+    );
+}
+
+fn check_glued_operators(src: &str, expect: Expect) {
+    let actual: String = glue_operators(tokenize(src))
+        .map(|token| format!("{} {}\n", token.kind, token.len))
+        .collect();
+    expect.assert_eq(&actual)
+}
+
+#[test]
+fn glued_operators() {
+    check_glued_operators(
+        "a..b == c ~= ... <= >= << >> ::x::",
+        expect![[r#"
+            identifier 1
+            .. 2
+            identifier 1
+            whitespace 1
+            == 2
+            whitespace 1
+            identifier 1
+            whitespace 1
+            ~= 2
+            whitespace 1
+            ... 3
+            whitespace 1
+            <= 2
+            whitespace 1
+            >= 2
+            whitespace 1
+            << 2
+            whitespace 1
+            >> 2
+            whitespace 1
+            :: 2
+            identifier 1
+            :: 2
+        "#]],
+    );
+    check_glued_operators(
+        "<<= === .... ~~=",
+        expect![[r#"
+            << 2
+            = 1
+            whitespace 1
+            == 2
+            = 1
+            whitespace 1
+            ... 3
+            . 1
+            whitespace 1
+            ~ 1
+            ~= 2
+        "#]],
+    );
+}
+
+#[test]
+fn operator_precedence() {
+    let (pow, pow_prec, pow_assoc) = binary_op(Caret).unwrap();
+    let (mul, mul_prec, _) = binary_op(Star).unwrap();
+    assert_eq!((pow, mul), (BinOp::Pow, BinOp::Mul));
+    assert!(pow_prec > mul_prec);
+    assert_eq!(pow_assoc, Assoc::Right);
+    // `-x ^ 2` is `-(x ^ 2)`, but `-x * 2` is `(-x) * 2`.
+    assert!(pow_prec > UNARY_PRECEDENCE && UNARY_PRECEDENCE > mul_prec);
+
+    let (concat, concat_prec, concat_assoc) = binary_op(DotDot).unwrap();
+    assert_eq!(concat, BinOp::Concat);
+    assert_eq!(concat_assoc, Assoc::Right);
+    assert!(concat_prec < binary_op(Plus).unwrap().1);
+    assert!(binary_op(Dot).is_none());
+
+    let (_, or_prec, _) = keyword_binary_op("or").unwrap();
+    let (_, and_prec, _) = keyword_binary_op("and").unwrap();
+    let (_, eq_prec, _) = binary_op(EqEq).unwrap();
+    assert!(or_prec < and_prec && and_prec < eq_prec);
+    assert_eq!(binary_op(Lt).unwrap().1, eq_prec);
+
+    assert_eq!(unary_op(Minus), Some(UnOp::Neg));
+    assert_eq!(unary_op(Hash), Some(UnOp::Len));
+    assert_eq!(keyword_unary_op("not"), Some(UnOp::Not));
+    assert_eq!(keyword_unary_op("no"), None);
+}
+
+#[test]
+fn long_string_close_level_mismatch() {
+    check_lexing(
+        "[=[ ]==] ]=] [==[ ]=] ]==] [=[a]==]=] [[]=]]",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 1, terminated: true, skipped_leading_newline: false } }, len: 12 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 2, terminated: true, skipped_leading_newline: false } }, len: 13 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 1, terminated: true, skipped_leading_newline: false } }, len: 10 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 0, terminated: true, skipped_leading_newline: false } }, len: 6 }
+        "#]],
+    );
+    assert_eq!(long_bracket_content("[=[a]==]=]", 1), "a]==");
+    assert_eq!(long_bracket_content("[==[ ]=] ]==]", 2), " ]=] ");
+}
+
+#[test]
+fn number_overflow() {
+    fn overflows(src: &str) -> bool {
+        let token = first_token(src);
+        assert_eq!(token.len as usize, src.len(), "{:?}", src);
+        let Literal { kind } = token.kind else {
+            panic!("{:?}", token.kind)
+        };
+        number_overflows(src, kind)
+    }
+
+    assert!(!overflows("42"));
+    assert!(!overflows("9223372036854775807"));
+    // Converted to the float 2^63 exactly.
+    assert!(!overflows("9223372036854775808"));
+    assert!(overflows("99999999999999999999"));
+    assert!(overflows(&"9".repeat(400)));
+    assert!(!overflows("1e308"));
+    assert!(overflows("1e309"));
+    assert!(!overflows("1e-400"));
+    assert!(!overflows("0xFFFFFFFFFFFFFFFF"));
+    assert!(!overflows("0x000000000000000000001"));
+    assert!(overflows("0xFFFFFFFFFFFFFFFF0"));
+    assert!(!overflows("0x1p1023"));
+    assert!(overflows("0x1p1024"));
+    assert!(overflows("0x10p1020"));
+    assert!(!overflows("0x0.1p1026"));
+    assert!(overflows("0x0.1p1028"));
+    assert!(!overflows("0x1.8p1023"));
+    assert!(!overflows("0x0p99999"));
+    assert!(!overflows("0x1p-99999999999999999999"));
+    assert!(overflows("0x1p99999999999999999999"));
+    assert!(!overflows("0x0p99999999999999999999"));
+    assert!(overflows("0x1p9223372036854775807"));
+    assert!(overflows("0xFFFFFFFFp9223372036854775800"));
+    assert!(!overflows("0x1p-9223372036854775808"));
+    // 2^128, 2^1023 and `f64::MAX` are exact floats, unlike 2^128 + 1 and
+    // 2^1024.
+    assert!(!overflows("340282366920938463463374607431768211456"));
+    assert!(overflows("340282366920938463463374607431768211457"));
+    let pow2_1023 = format!("{:.0}", 2f64.powi(1023));
+    assert!(!overflows(&pow2_1023));
+    assert!(overflows(&format!("{}0", pow2_1023)));
+    let mut max = format!("{:.0}", f64::MAX);
+    assert!(!overflows(&max));
+    // `f64::MAX` + 1.
+    assert!(max.ends_with('8'));
+    max.pop();
+    max.push('9');
+    assert!(overflows(&max));
+    // 2^64 and 3 * 2^70 are exact, but 2^64 + 2 isn't.
+    assert!(!overflows("18446744073709551616"));
+    assert!(!overflows("0003541774862152233910272"));
+    assert!(overflows("18446744073709551618"));
+    assert!(!overflows("0x0.000p+99999999999999999999"));
+    // `f64::MAX`, and numbers rounding to it or to infinity.
+    assert!(!overflows("0x1.fffffffffffffp1023"));
+    assert!(!overflows("0x1.fffffffffffff7ffp1023"));
+    assert!(overflows("0x1.fffffffffffff8p1023"));
+    assert!(overflows("0x1.fffffffffffffffffffp1023"));
+    assert!(overflows("0x0.ffffffffffffffffp1024"));
+
+    let options = LexOptions {
+        digit_separators: true,
+        ..LexOptions::default()
+    };
+    for (src, expected) in [
+        ("0xFF_FF", false),
+        ("1_000", false),
+        ("9_223_372_036_854_775_807", false),
+        ("99_999_999_999_999_999_999", true),
+        ("0xF_FFFF_FFFF_FFFF_FFFF", true),
+        ("1_0e308", true),
+    ] {
+        let token = tokenize_with(src, options).next().unwrap();
+        assert_eq!(token.len as usize, src.len(), "{:?}", src);
+        let Literal { kind } = token.kind else {
+            panic!("{:?}", token.kind)
+        };
+        assert_eq!(number_overflows(src, kind), expected, "{:?}", src);
+    }
+    let string = ShortString {
+        quote: QuoteKind::Single,
+        terminated: true,
+        has_invalid_escape: false,
+        crossed_newline: false,
+    };
+    assert!(!number_overflows("'1e999'", string));
+}
+
+#[test]
+fn carriage_return_in_short_string() {
+    check_lexing(
+        "\"abc\rdef\"",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: true }, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: Literal { kind: ShortString { quote: Double, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "'abc\r\ndef'",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 2 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 1 }
+        "#]],
+    );
+    check_lexing(
+        "'abc\\\r\ndef' 'abc\\\n\rdef' 'abc\\\rdef'",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 11 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 11 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 10 }
+        "#]],
+    );
+}
+
+#[test]
+fn checked_errors() {
+    let (tokens, errors) = tokenize_checked("x = 'abc\n0x & 1e+ 3x \"\\q");
+    assert_eq!(tokens.len(), 17);
+    expect![[r#"
+        [
+            LexError {
+                span: Span {
+                    lo: 4,
+                    hi: 8,
+                },
+                kind: UnterminatedString,
+            },
+            LexError {
+                span: Span {
+                    lo: 9,
+                    hi: 11,
+                },
+                kind: EmptyNumber,
+            },
+            LexError {
+                span: Span {
+                    lo: 12,
+                    hi: 13,
+                },
+                kind: UnknownToken,
+            },
+            LexError {
+                span: Span {
+                    lo: 14,
+                    hi: 16,
+                },
+                kind: EmptyExponent,
+            },
+            LexError {
+                span: Span {
+                    lo: 18,
+                    hi: 19,
+                },
+                kind: MalformedNumber,
+            },
+            LexError {
+                span: Span {
+                    lo: 21,
+                    hi: 24,
+                },
+                kind: InvalidEscape,
+            },
+            LexError {
+                span: Span {
+                    lo: 21,
+                    hi: 24,
+                },
+                kind: UnterminatedString,
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&errors);
+    let (_, errors) = tokenize_checked(SMOKE_TEST_SRC);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn checked_malformed_numbers() {
+    let kinds = |src, options| -> Vec<_> {
+        let (_, errors) = tokenize_checked_with(src, options);
+        errors.iter().map(|error| error.kind).collect()
+    };
+    let default = LexOptions::default();
+    for src in ["3x", "0x1g", "0x1p2.5"] {
+        assert_eq!(
+            kinds(src, default),
+            [LexErrorKind::MalformedNumber],
+            "{:?}",
+            src
+        );
+    }
+    assert_eq!(kinds("3 x 0x1p2 .5", default), []);
+
+    let options = LexOptions {
+        binary_octal_literals: true,
+        ..default
+    };
+    assert_eq!(kinds("0o18", options), [LexErrorKind::MalformedNumber]);
+    // `0o` has no digits, and `8` can't follow it.
+    assert_eq!(
+        kinds("0o8", options),
+        [LexErrorKind::EmptyNumber, LexErrorKind::MalformedNumber]
+    );
+    assert_eq!(kinds("0o7 0b1", options), []);
+}
+
+#[test]
+fn clone_tokens() {
+    let mut tokens = tokenize("a + b");
+    assert_eq!(tokens.next().map(|token| token.kind), Some(Ident));
+    let mut snapshot = tokens.clone();
+    tokens.next();
+    assert_eq!(tokens.next().map(|token| token.kind), Some(Plus));
+    assert_eq!(
+        snapshot.next().map(|token| token.kind),
+        Some(Whitespace {
+            has_newline: false,
+            has_unusual: false
+        })
+    );
+    assert_eq!(snapshot.count(), 3);
+    assert_eq!(tokens.count(), 2);
+}
+
+#[test]
+fn doc_comment() {
+    check_lexing(
+        "---@param x number\n---plain\n----@not\n--@not\n---@",
+        expect![[r#"
+            Token { kind: DocComment, len: 18 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 8 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 8 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: DocComment, len: 4 }
+        "#]],
+    );
+}
+
+#[test]
+fn single_token() {
+    assert_eq!(lex_single("foo"), Ok(Token::new(Ident, 3)));
+    assert_eq!(lex_single("=="), Ok(Token::new(EqEq, 2)));
+    assert_eq!(lex_single("[[a b]]").map(|token| token.len), Ok(7));
+    assert_eq!(
+        lex_single("a b"),
+        Err(SingleTokenError::Trailing { len: 1 })
+    );
+    assert_eq!(
+        lex_single("foo "),
+        Err(SingleTokenError::Trailing { len: 3 })
+    );
+    assert_eq!(lex_single(""), Err(SingleTokenError::Empty));
+}
+
+#[test]
+fn spans_with_base() {
+    let spans: Vec<_> = tokenize_with_base("x = 1", 100).collect();
+    assert_eq!(spans.len(), 5);
+    assert_eq!(spans[0], (Ident, Span::new(100, 101)));
+    assert_eq!(spans[2], (Eq, Span::new(102, 103)));
+    assert_eq!(spans[4].1, Span::new(104, 105));
+}
+
+#[test]
+fn glued_dot_sequences() {
+    check_glued_operators(
+        ". .. ... .... ..... ......",
+        expect![[r#"
+            . 1
+            whitespace 1
+            .. 2
+            whitespace 1
+            ... 3
+            whitespace 1
+            ... 3
+            . 1
+            whitespace 1
+            ... 3
+            .. 2
+            whitespace 1
+            ... 3
+            ... 3
+        "#]],
+    );
+}
+
+#[test]
+fn token_containing_offset() {
+    let tokens = tokenize_indexed("foo(bar)");
+    let find = |pos, bias| token_containing(&tokens, pos, bias);
+    // At the start of a token.
+    assert_eq!(find(0, Bias::Left), Some(0));
+    assert_eq!(find(0, Bias::Right), Some(0));
+    // At the end of `foo`, the start of `(`.
+    assert_eq!(find(3, Bias::Left), Some(0));
+    assert_eq!(find(3, Bias::Right), Some(1));
+    // Inside `bar`.
+    assert_eq!(find(5, Bias::Left), Some(2));
+    assert_eq!(find(5, Bias::Right), Some(2));
+    // At the end of the input.
+    assert_eq!(find(8, Bias::Left), Some(3));
+    assert_eq!(find(8, Bias::Right), Some(3));
+    assert_eq!(find(9, Bias::Left), None);
+    assert_eq!(token_containing(&[], 0, Bias::Right), None);
+}
+
+#[test]
+fn smoke_test_string_literals() {
+    let strings: Vec<_> = string_literals(SMOKE_TEST_SRC)
+        .map(|(span, shape)| (&SMOKE_TEST_SRC[span.lo as usize..span.hi as usize], shape))
+        .collect();
+    assert_eq!(
+        strings,
+        [
+            (
+                "\"\"",
+                StringShape::Short {
+                    quote: QuoteKind::Double
+                }
+            ),
+            (
+                "'c'",
+                StringShape::Short {
+                    quote: QuoteKind::Single
+                }
+            ),
+        ]
+    );
+    let strings: Vec<_> = string_literals("require [==[a]==]").collect();
+    assert_eq!(
+        strings,
+        [(Span::new(8, 17), StringShape::Long { level: 2 })]
+    );
+}
+
+#[test]
+fn long_string_leading_newline() {
+    check_lexing(
+        "[[\nfoo]] [[foo]] [=[\r\nfoo]=] [[ \nfoo]]",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 0, terminated: true, skipped_leading_newline: true } }, len: 8 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 0, terminated: true, skipped_leading_newline: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 1, terminated: true, skipped_leading_newline: true } }, len: 11 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: LongString { level: 0, terminated: true, skipped_leading_newline: false } }, len: 9 }
+        "#]],
+    );
+}
+
+#[test]
+fn compact_dump() {
+    let actual = dump_tokens_compact("local s = \"a\" .. 0x1p4 -- x\n");
+    expect![[r#"
+        Ident@0..5 "local"
+        Whitespace@5..6 " "
+        Ident@6..7 "s"
+        Whitespace@7..8 " "
+        Eq@8..9 "="
+        Whitespace@9..10 " "
+        ShortString@10..13 "\"a\""
+        Whitespace@13..14 " "
+        Dot@14..15 "."
+        Dot@15..16 "."
+        Whitespace@16..17 " "
+        Number@17..22 "0x1p4"
+        Whitespace@22..23 " "
+        ShortComment@23..27 "-- x"
+        Whitespace@27..28 "\n"
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn c_comments() {
+    let options = LexOptions {
+        c_comments: true,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "a // comment\n/* a */ b /**/ /*/ c */ 1 / 2 /* a",
+        options,
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 10 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: BlockComment { terminated: true }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: BlockComment { terminated: true }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: BlockComment { terminated: true }, len: 8 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Slash, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: BlockComment { terminated: false }, len: 4 }
+        "#]],
+    );
+}
+
+#[test]
+fn c_comments_disabled() {
+    check_lexing(
+        "a // b /* c */",
+        expect![[r#"
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: SlashSlash, len: 2 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Slash, len: 1 }
+            Token { kind: Star, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Star, len: 1 }
+            Token { kind: Slash, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn longest_token() {
+    let src = "local s = [==[\n a long string\n]==] .. 'short'";
+    let long_string = tokenize(src)
+        .find(|token| {
+            matches!(
+                token.kind,
+                Literal {
+                    kind: LongString { .. }
+                }
+            )
+        })
+        .unwrap();
+    assert_eq!(long_string.len, 24);
+    assert_eq!(max_token_len(src), long_string.len);
+    assert_eq!(max_token_len(""), 0);
+}
+
+#[test]
+fn spanned_token_set() {
+    use std::collections::HashSet;
+
+    let tokens: HashSet<_> = tokenize_spanned("a a a").collect();
+    assert_eq!(tokens.len(), 5);
+    let idents = tokens.iter().filter(|token| token.kind == Ident).count();
+    assert_eq!(idents, 3);
+    let again: HashSet<_> = tokenize_spanned("a a a").collect();
+    assert_eq!(tokens, again);
+}
+
+#[test]
+fn at_sign() {
+    check_lexing(
+        "@foo a@b",
+        expect![[r#"
+            Token { kind: At, len: 1 }
+            Token { kind: Ident, len: 3 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: At, len: 1 }
+            Token { kind: Ident, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn digit_separators() {
+    let options = LexOptions {
+        digit_separators: true,
+        ..LexOptions::default()
+    };
+    check_lexing_with(
+        "1_000 0xFF_FF 1__0 1_ 0x_1 1.5_5e1_0 1e_5 _5",
+        options,
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: true } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: true } }, len: 2 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Hexadecimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: false, misplaced_separator: true } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 9 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: true } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Ident, len: 2 }
+        "#]],
+    );
+}
+
+#[test]
+fn digit_separators_disabled() {
+    check_lexing(
+        "1_000",
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: None, is_float: false, leading_zero: false, malformed_suffix: true, misplaced_separator: false } }, len: 1 }
+            Token { kind: Ident, len: 4 }
+        "#]],
+    );
+}
+
+#[test]
+fn token_stream() {
+    let stream = TokenStream::new("print('hi')");
+    assert_eq!(stream.len(), 4);
+    assert_eq!(stream.slice(0), "print");
+    assert_eq!(stream.slice(2), "'hi'");
+    assert_eq!(stream[3].kind, CloseParen);
+    assert_eq!(stream[3].span(), Span::new(10, 11));
+    assert!(stream.get(4).is_none());
+    assert!(TokenStream::new("").is_empty());
+}
+
+#[test]
+#[should_panic]
+fn token_stream_slice_out_of_bounds() {
+    TokenStream::new("x").slice(1);
+}
+
+#[test]
+fn nul_in_strings() {
+    check_lexing(
+        "[[a\0b]] 'a\0b' --[[\0]] -- \0",
+        expect![[r#"
+            Token { kind: Literal { kind: LongString { level: 0, terminated: true, skipped_leading_newline: false } }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: true, has_invalid_escape: false, crossed_newline: false } }, len: 5 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: LongComment { level: 0, terminated: true }, len: 7 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 4 }
+        "#]],
+    );
+    check_lexing(
+        "'a\0",
+        expect![[r#"
+            Token { kind: Literal { kind: ShortString { quote: Single, terminated: false, has_invalid_escape: false, crossed_newline: false } }, len: 3 }
+        "#]],
+    );
+}
+
+#[test]
+fn quote_kind_char() {
+    let quotes: Vec<_> = string_literals(r#"'a' "b""#)
+        .map(|(_, shape)| match shape {
+            StringShape::Short { quote } => quote,
+            StringShape::Long { .. } => unreachable!(),
+        })
+        .collect();
+    assert_eq!(quotes, [QuoteKind::Single, QuoteKind::Double]);
+    assert_eq!(QuoteKind::Single.as_char(), '\'');
+    assert_eq!(QuoteKind::Double.as_char(), '"');
+}
+
+#[test]
+fn tokenize_limited_tokens() {
+    assert_eq!(tokenize_limited("a b", 3).unwrap().len(), 3);
+    assert_eq!(tokenize_limited("", 0), Ok(Vec::new()));
+
+    let err = tokenize_limited("local x = 1", 2).unwrap_err();
+    assert_eq!(
+        err.tokens,
+        [
+            Token::new(Ident, 5),
+            Token::new(
+                Whitespace {
+                    has_newline: false,
+                    has_unusual: false
+                },
+                1
+            ),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_limited_bytes_huge_token() {
+    assert_eq!(tokenize_limited_bytes("a b", 3).unwrap().len(), 3);
+
+    let src = format!("x = '{}", "a".repeat(1 << 20));
+    let err = tokenize_limited_bytes(&src, 64).unwrap_err();
+    let kinds: Vec<_> = err.tokens.iter().map(|token| token.kind).collect();
+    assert_eq!(kinds.len(), 4);
+    assert_eq!(kinds[2], Eq);
+
+    // The limit is moved back to a char boundary.
+    let err = tokenize_limited_bytes("x ππ", 5).unwrap_err();
+    assert_eq!(err.tokens.len(), 1);
+}
+
+#[test]
+fn tokenize_limited_bytes_lookahead() {
+    // `3e+` alone is lexed as `3e`, `+`, so the tokens before the cut
+    // that depend on the chars after it are left out.
+    let src = "x = 3e+5";
+    let expected = tokenize_to_vec(src);
+    for max_bytes in 0..src.len() {
+        let err = tokenize_limited_bytes(src, max_bytes).unwrap_err();
+        assert_eq!(err.tokens, expected[..err.tokens.len()], "{}", max_bytes);
+    }
+    let err = tokenize_limited_bytes(src, 7).unwrap_err();
+    assert_eq!(err.tokens.len(), 4);
+}
+
+#[test]
+fn verify_coverage_of_tokens() {
+    let src = "local s = 'π'";
+    let mut tokens = tokenize_indexed(src);
+    assert_eq!(verify_coverage(src, &tokens), Ok(()));
+    assert_eq!(verify_coverage("", &[]), Ok(()));
+
+    // `local` is shortened, leaving a gap before the whitespace.
+    tokens[0].len = 4;
+    assert_eq!(
+        verify_coverage(src, &tokens),
+        Err(CoverageError::Gap {
+            span: Span::new(4, 5)
+        })
+    );
+
+    // `local` is extended over the whitespace after it.
+    tokens[0].len = 6;
+    assert_eq!(
+        verify_coverage(src, &tokens),
+        Err(CoverageError::Overlap {
+            span: Span::new(5, 6)
+        })
+    );
+
+    tokens[0].len = 5;
+    let string = tokens.len() - 1;
+    tokens[string].len = 2;
+    assert_eq!(
+        verify_coverage(src, &tokens),
+        Err(CoverageError::NotCharBoundary { pos: 12 })
+    );
+    tokens[string].len = 10;
+    assert_eq!(
+        verify_coverage(src, &tokens),
+        Err(CoverageError::OutOfBounds {
+            span: Span::new(10, 20)
+        })
+    );
+    tokens.pop();
+    assert_eq!(
+        verify_coverage(src, &tokens),
+        Err(CoverageError::Gap {
+            span: Span::new(10, 14)
+        })
+    );
+}
+
+#[test]
+fn suspicious_c_operators() {
+    fn check(src: &str) -> Vec<&str> {
+        suspicious_operators(src)
+            .into_iter()
+            .map(|span| &src[span.lo as usize..span.hi as usize])
+            .collect()
+    }
+    assert_eq!(check("a && b || c"), ["&&", "||"]);
+    assert_eq!(check("if !x then end"), ["!"]);
+    assert_eq!(check("a != b; a!=b"), ["!=", "!="]);
+    assert_eq!(check("!x!=y"), ["!", "!="]);
+    assert!(check("a & b | c ~= d").is_empty());
+    assert!(check("'&&' -- !x").is_empty());
+}
+
+#[test]
+fn exponent_sign_without_digits() {
+    check_lexing(
+        "3e+ 3e+5 3e+x 3e-",
+        expect![[r#"
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Plus, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: false, exponent_sign: Some('+'), is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 4 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Plus, len: 1 }
+            Token { kind: Ident, len: 1 }
+            Token { kind: Whitespace { has_newline: false, has_unusual: false }, len: 1 }
+            Token { kind: Literal { kind: Number { base: Decimal, empty_number: false, empty_exponent: true, exponent_sign: None, is_float: true, leading_zero: false, malformed_suffix: false, misplaced_separator: false } }, len: 2 }
+            Token { kind: Minus, len: 1 }
+        "#]],
+    );
+}
+
+#[test]
+fn token_kind_categories() {
+    let kinds: Vec<_> = tokenize("0x1 1.5 'a' [[b]] -- c\n--[[ d ]] e #!")
+        .map(|token| token.kind)
+        .filter(|kind| !matches!(kind, Whitespace { .. }))
+        .collect();
+    let categories: Vec<_> = kinds
+        .iter()
+        .map(|kind| (kind.is_number(), kind.is_string(), kind.is_comment()))
+        .collect();
+    assert_eq!(
+        categories,
+        [
+            (true, false, false),
+            (true, false, false),
+            (false, true, false),
+            (false, true, false),
+            (false, false, true),
+            (false, false, true),
+            (false, false, false),
+            (false, false, false),
+            (false, false, false),
+        ]
+    );
+    assert!(!Shebang.is_comment());
+}
+
+#[test]
+fn lexer_checkpoint_and_restore() {
+    let mut lexer = Lexer::new("f(x, y)");
+    lexer.next_token();
+    lexer.next_token();
+    let checkpoint = lexer.checkpoint();
+    let next = lexer.next_token();
+    assert_eq!(next, Some(Token::new(Ident, 1)));
+    lexer.next_token();
+    lexer.next_token();
+    assert_eq!(lexer.remaining(), "y)");
+
+    lexer.restore(checkpoint.clone());
+    assert_eq!(lexer.remaining(), "x, y)");
+    assert_eq!(lexer.next_token(), next);
+    lexer.restore(checkpoint);
+    assert_eq!(std::iter::from_fn(|| lexer.next_token()).count(), 5);
+}
+
+#[test]
+fn suspicious_mid_file_shebang() {
+    let src = "#!/usr/bin/env lua\nprint(#t)\n#!/usr/bin/env lua\n";
+    let spans = suspicious_operators(src);
+    assert_eq!(spans, [Span::new(29, 31)]);
+    assert_eq!(&src[29..31], "#!");
+    assert_eq!(suspicious_operators("n = #!x"), [Span::new(4, 6)]);
+}
+
+#[test]
+fn tokenize_lines_multi_line_comment() {
+    fn check(src: &str, expect: Expect) {
+        let mut actual = String::new();
+        let text =
+            |token: &SpannedToken| &src[token.start as usize..(token.start + token.len) as usize];
+        for line in tokenize_lines(src) {
+            let texts: Vec<_> = line.tokens.iter().map(text).collect();
+            let continued = line.continued.as_ref().map(text);
+            actual += &format!(
+                "{}@{}: {:?} {:?}\n",
+                line.line, line.start, continued, texts
+            );
+        }
+        expect.assert_eq(&actual)
+    }
+
+    check(
+        "x = 1 --[[ a\nb\nc ]] y\r\nz\n",
+        expect![[r#"
+            0@0: None ["x", " ", "=", " ", "1", " ", "--[[ a\nb\nc ]]"]
+            1@13: Some("--[[ a\nb\nc ]]") []
+            2@15: Some("--[[ a\nb\nc ]]") [" ", "y", "\r\n"]
+            3@23: None ["z", "\n"]
+        "#]],
+    );
+    check(
+        "--[[\n\n]]",
+        expect![[r#"
+            0@0: None ["--[[\n\n]]"]
+            1@5: Some("--[[\n\n]]") []
+            2@6: Some("--[[\n\n]]") []
+        "#]],
+    );
+    check("", expect![[""]]);
+    check(
+        "x\n  y\r\rz",
+        expect![[r#"
+            0@0: None ["x", "\n  "]
+            1@2: Some("\n  ") ["y", "\r\r"]
+            2@6: Some("\r\r") []
+            3@7: None ["z"]
+        "#]],
+    );
+}
+
+#[test]
+fn stable_tags() {
+    // Tags are a part of the public API. Never change the expected values,
+    // only add new ones.
+    let tags: Vec<_> = tokenize("--x\n--[[]] a 'b' ; ( ] # ~ : = < > - + / ^ % @ &")
+        .map(|token| token.kind)
+        .filter(|kind| !matches!(kind, Whitespace { .. }))
+        .map(|kind| kind.tag())
+        .collect();
+    assert_eq!(
+        tags,
+        [0, 2, 6, 7, 8, 13, 18, 19, 20, 22, 24, 26, 29, 32, 33, 35, 37, 38, 39, 40]
+    );
+    let whitespace = Whitespace {
+        has_newline: false,
+        has_unusual: false,
+    };
+    assert_eq!(whitespace.tag(), 4);
+    assert_eq!(Shebang.tag(), 5);
+    assert_eq!(DotDotDot.tag(), 12);
+    assert_eq!(GtGt.tag(), 31);
+
+    let literal_tags: Vec<_> = tokenize("1 'a' [[b]]")
+        .filter_map(|token| match token.kind {
+            Literal { kind } => Some(kind.tag()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(literal_tags, [0, 1, 2]);
+}
+
+#[test]
+fn incomplete_long_comment_bracket() {
+    check_lexing(
+        "--[\n--[=\n--[==x\n--[==",
+        expect![[r#"
+            Token { kind: ShortComment, len: 3 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 4 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 6 }
+            Token { kind: Whitespace { has_newline: true, has_unusual: false }, len: 1 }
+            Token { kind: ShortComment, len: 5 }
+        "#]],
+    );
+    check_lexing(
+        "--[=",
+        expect![[r#"
+            Token { kind: ShortComment, len: 4 }
+        "#]],
+    );
+}
+
+#[test]
+fn span_manipulation() {
+    let span = Span::new(2, 5);
+    assert_eq!(span.with_lo(0), Span::new(0, 5));
+    assert_eq!(span.with_hi(9), Span::new(2, 9));
+    assert_eq!(span.shrink_to_lo(), Span::new(2, 2));
+    assert_eq!(span.shrink_to_hi(), Span::new(5, 5));
+
+    assert_eq!(span.between(Span::new(8, 9)), Span::new(5, 8));
+    // Adjacent spans.
+    assert_eq!(span.between(Span::new(5, 6)), Span::new(5, 5));
+    // Overlapping spans.
+    assert_eq!(span.between(Span::new(3, 6)), Span::new(5, 5));
+}
+
+#[test]
+fn mixed_indentation_lines() {
+    assert_eq!(
+        mixed_indentation("if x then\n  \ty()\nend"),
+        [Span::new(10, 13)]
+    );
+    assert_eq!(
+        mixed_indentation("if x then\n\t  y()\nend"),
+        [Span::new(10, 13)]
+    );
+    assert!(mixed_indentation("if x then\n    y()\nend").is_empty());
+    assert!(mixed_indentation("if x then\n\t\ty()\nend").is_empty());
+
+    // First and blank lines count, but not whitespace in the middle or at
+    // the end of a line, or inside a long string.
+    assert_eq!(
+        mixed_indentation(" \tx\n\t \r\n  \t"),
+        [Span::new(0, 2), Span::new(4, 6), Span::new(8, 11)]
+    );
+    assert!(mixed_indentation("x = \t 1 \t\ns = [[\n \t]]").is_empty());
+}